@@ -4,6 +4,8 @@ use std::fs;
 use libdrm_amdgpu_sys::PCI;
 use libdrm_amdgpu_sys::AMDGPU::{self, PowerCap, PowerProfile};
 
+use crate::backend::Backend;
+
 pub struct AmdgpuDevice {
     pub pci_bus: PCI::BUS_INFO,
     pub sysfs_path: PathBuf,
@@ -19,15 +21,24 @@ pub struct AmdgpuDevice {
     pub fan_minimum_pwm: Option<FanMinPwm>, // RDNA 3/4
     pub sclk_offset: Option<SclkOffset>, // RDNA 4
     pub vddgfx_offset: Option<VddgfxOffset>, // RDNA 2/3/4
+    pub sclk_limits: Option<ClockLimits>, // RDNA 2/3, Vega20
+    pub mclk_limits: Option<ClockLimits>, // RDNA 2/3, Vega20
+    pub vddc_curve: Option<VddcCurve>, // Vega20
+    pub sclk_states: Option<ClockVoltageStates>, // Polaris, Vega10
+    pub mclk_states: Option<ClockVoltageStates>, // Polaris, Vega10
+    pub sclk_od: Option<OdPercent>, // GCN
+    pub mclk_od: Option<OdPercent>, // GCN
+    pub dpm_levels: Vec<DpmLevels>,
     pub fan_zero_rpm: Option<FanZeroRpm>, // RDNA 3/4
     pub fan_curve: Option<FanCurve>,
     pub acoustic_target_rpm_threshold: Option<AcousticTargetRpmThreshold>, // RDNA 3/4
+    pub hwmon_pwm: Option<HwmonPwm>,
     pub runtime_status_path: PathBuf,
 }
 
 impl AmdgpuDevice {
     pub fn get_from_pci_bus(pci_bus: PCI::BUS_INFO) -> Option<Self> {
-        let sysfs_path = pci_bus.get_sysfs_path();
+        let sysfs_path = Backend::get().get_sysfs_path(&pci_bus);
         let power_profile_path = sysfs_path.join("pp_power_profile_mode");
         let dpm_perf_level_path = sysfs_path.join("power_dpm_force_performance_level");
 
@@ -46,22 +57,38 @@ impl AmdgpuDevice {
         };
         let device_name = AMDGPU::find_device_name(device_id, revision_id)
             .unwrap_or(AMDGPU::DEFAULT_DEVICE_NAME.to_string());
-        let hwmon_path = pci_bus.get_hwmon_path()?;
+        let hwmon_path = Backend::get().get_hwmon_path(&pci_bus)?;
         let power_cap = PowerCap::from_hwmon_path(&hwmon_path);
         let power_cap_path = hwmon_path.join("power1_cap");
+        let hwmon_pwm = HwmonPwm::from_hwmon_path(&hwmon_path);
         let fan_target_temperature = FanTargetTemp::from_sysfs_path(&sysfs_path);
         let fan_minimum_pwm = FanMinPwm::from_sysfs_path(&sysfs_path);
         let pp_od_clk_voltage_path = sysfs_path.join("pp_od_clk_voltage");
         let pp_od_clk_voltage = fs::read_to_string(&pp_od_clk_voltage_path);
-        let (sclk_offset, vddgfx_offset) = if let Ok(s) = pp_od_clk_voltage {
-            (SclkOffset::from_str(&s), VddgfxOffset::from_str(&s))
-        } else {
-            (None, None)
-        };
+        let (sclk_offset, vddgfx_offset, sclk_limits, mclk_limits, vddc_curve, sclk_states, mclk_states) =
+            if let Ok(ref s) = pp_od_clk_voltage {
+                (
+                    SclkOffset::from_str(s),
+                    VddgfxOffset::from_str(s),
+                    ClockLimits::sclk_from_str(s),
+                    ClockLimits::mclk_from_str(s),
+                    VddcCurve::from_str(s),
+                    ClockVoltageStates::sclk_from_str(s),
+                    ClockVoltageStates::mclk_from_str(s),
+                )
+            } else {
+                (None, None, None, None, None, None, None)
+            };
+        let sclk_od = OdPercent::from_sysfs_path(&sysfs_path, "pp_sclk_od");
+        let mclk_od = OdPercent::from_sysfs_path(&sysfs_path, "pp_mclk_od");
         let fan_zero_rpm = FanZeroRpm::from_sysfs_path(&sysfs_path);
         let acoustic_target_rpm_threshold = AcousticTargetRpmThreshold::from_sysfs_path(&sysfs_path);
         let runtime_status_path = sysfs_path.join("power/runtime_status");
         let fan_curve = FanCurve::from_sysfs_path(&sysfs_path);
+        let dpm_levels = DpmClockType::ALL
+            .iter()
+            .filter_map(|clk_type| DpmLevels::from_sysfs_path(&sysfs_path, *clk_type))
+            .collect();
 
         Some(Self {
             pci_bus,
@@ -78,9 +105,18 @@ impl AmdgpuDevice {
             fan_minimum_pwm,
             sclk_offset,
             vddgfx_offset,
+            sclk_limits,
+            mclk_limits,
+            vddc_curve,
+            sclk_states,
+            mclk_states,
+            sclk_od,
+            mclk_od,
+            dpm_levels,
             fan_zero_rpm,
             fan_curve,
             acoustic_target_rpm_threshold,
+            hwmon_pwm,
             runtime_status_path,
         })
     }
@@ -97,6 +133,34 @@ impl AmdgpuDevice {
         PowerProfile::get_all_supported_profiles_from_sysfs(&self.sysfs_path)
     }
 
+    pub fn has_od_clk_voltage(&self) -> bool {
+        self.sclk_offset.is_some()
+        || self.vddgfx_offset.is_some()
+        || self.sclk_limits.is_some()
+        || self.mclk_limits.is_some()
+        || self.vddc_curve.is_some()
+        || self.sclk_states.is_some()
+        || self.mclk_states.is_some()
+    }
+
+    /// Files in `gpu_od/fan_ctrl/`
+    pub fn fan_ctrl_paths(&self) -> Vec<&PathBuf> {
+        [
+            self.fan_curve.as_ref().map(|f| &f.path),
+            self.fan_target_temperature.as_ref().map(|f| &f.path),
+            self.fan_minimum_pwm.as_ref().map(|f| &f.path),
+            self.acoustic_target_rpm_threshold.as_ref().map(|f| &f.path),
+            self.fan_zero_rpm.as_ref().map(|f| &f.path),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    pub fn get_power_profile_table(&self) -> Option<PowerProfileTable> {
+        PowerProfileTable::from_sysfs_path(&self.sysfs_path)
+    }
+
     pub fn check_if_device_is_active(&self) -> bool {
         let Ok(s) = fs::read_to_string(&self.runtime_status_path) else { return false };
 
@@ -104,6 +168,67 @@ impl AmdgpuDevice {
     }
 }
 
+/// `pwm1`, `pwm1_enable` and `temp*_input` in hwmon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HwmonPwm {
+    pub pwm_path: PathBuf,
+    pub pwm_enable_path: PathBuf,
+    pub temps: Vec<(String, PathBuf)>, // (label, path)
+}
+
+impl HwmonPwm {
+    pub fn from_hwmon_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+        let path = path.into();
+        let pwm_path = path.join("pwm1");
+        let pwm_enable_path = path.join("pwm1_enable");
+
+        if !pwm_path.exists() || !pwm_enable_path.exists() {
+            return None;
+        }
+
+        let temps: Vec<(String, PathBuf)> = ["edge", "junction", "mem"]
+            .iter()
+            .enumerate()
+            .filter_map(|(i, default_label)| {
+                let input = path.join(format!("temp{}_input", i+1));
+
+                if !input.exists() {
+                    return None;
+                }
+
+                let label = fs::read_to_string(path.join(format!("temp{}_label", i+1)))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or(default_label.to_string());
+
+                Some((label, input))
+            })
+            .collect();
+
+        if temps.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pwm_path,
+            pwm_enable_path,
+            temps,
+        })
+    }
+
+    /// [C]
+    pub fn read_temp(&self, label: &str) -> Option<u32> {
+        let (_, path) = self.temps.iter().find(|(l, _)| l == label)?;
+        let millidegree: u32 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+
+        Some(millidegree / 1000)
+    }
+
+    /// [0, 255]
+    pub fn read_pwm(&self) -> Option<u32> {
+        fs::read_to_string(&self.pwm_path).ok()?.trim().parse().ok()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FanZeroRpm {
     pub path: PathBuf,
@@ -166,7 +291,7 @@ impl SclkOffset {
         })
     }
 
-    pub fn _from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+    pub fn from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
         let s = fs::read_to_string(path.into().join("pp_od_clk_voltage")).ok()?;
 
         Self::from_str(&s)
@@ -210,11 +335,534 @@ impl VddgfxOffset {
         })
     }
 
-    pub fn _from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+    pub fn from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+        let s = fs::read_to_string(path.into().join("pp_od_clk_voltage")).ok()?;
+
+        Self::from_str(&s)
+    }
+}
+
+/// `OD_SCLK`/`OD_MCLK` min/max tables and `OD_RANGE` in `pp_od_clk_voltage`
+/// ```text
+/// OD_SCLK:
+/// 0: 500Mhz
+/// 1: 2800Mhz
+/// OD_MCLK:
+/// 0: 97Mhz
+/// 1: 1000MHz
+/// OD_RANGE:
+/// SCLK:     500Mhz       3150Mhz
+/// MCLK:     674Mhz       1200Mhz
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockLimits {
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub range: Option<[u32; 2]>,
+}
+
+fn parse_mhz(s: &str) -> Option<u32> {
+    s.strip_suffix("Mhz").or_else(|| s.strip_suffix("MHz"))?.parse().ok()
+}
+
+fn parse_mv(s: &str) -> Option<u32> {
+    s.strip_suffix("mV")?.parse().ok()
+}
+
+/// "<min> <max>"
+fn parse_range(s: &str, parse: fn(&str) -> Option<u32>) -> Option<[u32; 2]> {
+    let mut split = s.split_whitespace();
+    let [min, max] = [split.next(), split.next()].map(|v| v.and_then(parse));
+
+    Some([min?, max?])
+}
+
+/// "<index>: <clock>Mhz <voltage>mV" lines after `section` in `pp_od_clk_voltage`
+fn parse_clock_voltage_points(s: &str, section: &str) -> Option<Vec<(u32, u32)>> {
+    let mut points: Vec<(u32, u32)> = Vec::new();
+    let mut lines = s.lines().skip_while(|l| *l != section).skip(1);
+
+    while let Some(l) = lines.next() && !l.ends_with(':') {
+        let (_index, val) = l.split_once(':')?;
+        let mut val = val.split_whitespace();
+        let clk = val.next().and_then(parse_mhz)?;
+        let volt = val.next().and_then(parse_mv)?;
+
+        points.push((clk, volt));
+    }
+
+    if points.is_empty() {
+        return None;
+    }
+
+    Some(points)
+}
+
+impl ClockLimits {
+    pub fn sclk_from_str(s: &str) -> Option<Self> {
+        Self::from_str(s, "OD_SCLK:", "SCLK:")
+    }
+
+    pub fn mclk_from_str(s: &str) -> Option<Self> {
+        Self::from_str(s, "OD_MCLK:", "MCLK:")
+    }
+
+    fn from_str(s: &str, section: &str, range_key: &str) -> Option<Self> {
+        let mut min: Option<u32> = None;
+        let mut max: Option<u32> = None;
+
+        {
+            let mut lines = s.lines().skip_while(|l| *l != section).skip(1);
+
+            while let Some(l) = lines.next() && !l.ends_with(':') {
+                let (index, val) = l.split_once(':')?;
+                let mut val = val.split_whitespace();
+                let clk = val.next().and_then(parse_mhz)?;
+
+                // per-state voltage tables (Polaris/Vega10) are not min/max limits
+                if val.next().is_some() {
+                    return None;
+                }
+
+                match index.trim() {
+                    "0" => min = Some(clk),
+                    "1" => max = Some(clk),
+                    _ => return None,
+                }
+            }
+        }
+
+        if min.is_none() && max.is_none() {
+            return None;
+        }
+
+        let range = s
+            .lines()
+            .skip_while(|l| *l != "OD_RANGE:")
+            .find_map(|l| l.strip_prefix(range_key))
+            .and_then(|l| parse_range(l, parse_mhz));
+
+        Some(Self {
+            min,
+            max,
+            range,
+        })
+    }
+
+    pub fn sclk_from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+        let s = fs::read_to_string(path.into().join("pp_od_clk_voltage")).ok()?;
+
+        Self::sclk_from_str(&s)
+    }
+
+    pub fn mclk_from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+        let s = fs::read_to_string(path.into().join("pp_od_clk_voltage")).ok()?;
+
+        Self::mclk_from_str(&s)
+    }
+
+    pub fn contains(&self, clk: u32) -> bool {
+        self.range.is_none_or(|[min, max]| (min..=max).contains(&clk))
+    }
+}
+
+/// `OD_VDDC_CURVE` points and their ranges in `pp_od_clk_voltage` (Vega20)
+/// ```text
+/// OD_VDDC_CURVE:
+/// 0: 808Mhz 724mV
+/// 1: 1304Mhz 822mV
+/// 2: 1801Mhz 1061mV
+/// OD_RANGE:
+/// VDDC_CURVE_SCLK[0]:     808Mhz       2200Mhz
+/// VDDC_CURVE_VOLT[0]:     738mV        1218mV
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VddcCurve {
+    pub points: Vec<(u32, u32)>, // (SCLK [MHz], voltage [mV])
+    pub ranges: Vec<([u32; 2], [u32; 2])>, // (SCLK range, voltage range) for each point
+}
+
+impl VddcCurve {
+    pub fn from_str(s: &str) -> Option<Self> {
+        let points = parse_clock_voltage_points(s, "OD_VDDC_CURVE:")?;
+        let ranges = (0..points.len())
+            .map_while(|i| {
+                let sclk = format!("VDDC_CURVE_SCLK[{i}]:");
+                let volt = format!("VDDC_CURVE_VOLT[{i}]:");
+                let sclk = s.lines().find_map(|l| l.strip_prefix(&sclk))?;
+                let volt = s.lines().find_map(|l| l.strip_prefix(&volt))?;
+
+                Some((parse_range(sclk, parse_mhz)?, parse_range(volt, parse_mv)?))
+            })
+            .collect();
+
+        Some(Self {
+            points,
+            ranges,
+        })
+    }
+
+    pub fn from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+        let s = fs::read_to_string(path.into().join("pp_od_clk_voltage")).ok()?;
+
+        Self::from_str(&s)
+    }
+
+    pub fn contains(&self, index: usize, (clk, volt): (u32, u32)) -> bool {
+        self.ranges.get(index).is_none_or(|([min_clk, max_clk], [min_volt, max_volt])| {
+            (*min_clk..=*max_clk).contains(&clk) && (*min_volt..=*max_volt).contains(&volt)
+        })
+    }
+}
+
+/// Per-state `OD_SCLK`/`OD_MCLK` voltage tables in `pp_od_clk_voltage` (Polaris, Vega10)
+/// ```text
+/// OD_SCLK:
+/// 0:        300MHz        750mV
+/// 1:        608MHz        818mV
+/// OD_MCLK:
+/// 0:        300MHz        750mV
+/// 1:       2000MHz        900mV
+/// OD_RANGE:
+/// SCLK:     300MHz       2000MHz
+/// MCLK:     300MHz       2250MHz
+/// VDDC:     750mV        1150mV
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockVoltageStates {
+    pub states: Vec<(u32, u32)>, // (clock [MHz], voltage [mV])
+    pub clk_range: Option<[u32; 2]>,
+    pub volt_range: Option<[u32; 2]>,
+}
+
+impl ClockVoltageStates {
+    pub fn sclk_from_str(s: &str) -> Option<Self> {
+        Self::from_str(s, "OD_SCLK:", "SCLK:")
+    }
+
+    pub fn mclk_from_str(s: &str) -> Option<Self> {
+        Self::from_str(s, "OD_MCLK:", "MCLK:")
+    }
+
+    fn from_str(s: &str, section: &str, range_key: &str) -> Option<Self> {
+        let states = parse_clock_voltage_points(s, section)?;
+        let range = |key: &str, parse: fn(&str) -> Option<u32>| {
+            s.lines()
+                .skip_while(|l| *l != "OD_RANGE:")
+                .find_map(|l| l.strip_prefix(key))
+                .and_then(|l| parse_range(l, parse))
+        };
+
+        Some(Self {
+            states,
+            clk_range: range(range_key, parse_mhz),
+            volt_range: range("VDDC:", parse_mv),
+        })
+    }
+
+    pub fn sclk_from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+        let s = fs::read_to_string(path.into().join("pp_od_clk_voltage")).ok()?;
+
+        Self::sclk_from_str(&s)
+    }
+
+    pub fn mclk_from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
         let s = fs::read_to_string(path.into().join("pp_od_clk_voltage")).ok()?;
 
+        Self::mclk_from_str(&s)
+    }
+
+    pub fn contains(&self, (clk, volt): (u32, u32)) -> bool {
+        self.clk_range.is_none_or(|[min, max]| (min..=max).contains(&clk))
+            && self.volt_range.is_none_or(|[min, max]| (min..=max).contains(&volt))
+    }
+}
+
+/// Overclocking percentage in `pp_sclk_od`/`pp_mclk_od` (GCN)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OdPercent {
+    pub path: PathBuf,
+    pub current: u32,
+}
+
+impl OdPercent {
+    pub fn from_sysfs_path<P: Into<PathBuf>>(path: P, file_name: &str) -> Option<Self> {
+        let path = path.into().join(file_name);
+        let current = fs::read_to_string(&path).ok()?.trim().parse().ok()?;
+
+        Some(Self {
+            path,
+            current,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DpmClockType {
+    Sclk,
+    Mclk,
+    Fclk,
+    Socclk,
+    Pcie,
+}
+
+impl DpmClockType {
+    pub const ALL: [Self; 5] = [Self::Sclk, Self::Mclk, Self::Fclk, Self::Socclk, Self::Pcie];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sclk => "sclk",
+            Self::Mclk => "mclk",
+            Self::Fclk => "fclk",
+            Self::Socclk => "socclk",
+            Self::Pcie => "pcie",
+        }
+    }
+}
+
+/// DPM levels in `pp_dpm_{sclk,mclk,fclk,socclk,pcie}`
+/// ```text
+/// 0: 500Mhz
+/// 1: 1000Mhz *
+/// 2: 2500Mhz
+/// ```
+/// ```text
+/// 0: 2.5GT/s, x16 619Mhz
+/// 1: 16.0GT/s, x16 1000Mhz *
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpmLevels {
+    pub clk_type: DpmClockType,
+    pub path: PathBuf,
+    pub levels: Vec<(u32, String)>,
+}
+
+impl DpmLevels {
+    pub fn from_str(s: &str, clk_type: DpmClockType, path: PathBuf) -> Option<Self> {
+        let levels: Vec<(u32, String)> = s
+            .lines()
+            .filter_map(|l| {
+                let (index, val) = l.split_once(':')?;
+                // skip the deep sleep level ("S: 27Mhz"), it can't be selected
+                let index = index.trim().parse::<u32>().ok()?;
+                let val = val.trim().trim_end_matches('*').trim_end();
+
+                Some((index, val.to_string()))
+            })
+            .collect();
+
+        if levels.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            clk_type,
+            path,
+            levels,
+        })
+    }
+
+    pub fn from_sysfs_path<P: Into<PathBuf>>(path: P, clk_type: DpmClockType) -> Option<Self> {
+        let path = path.into().join(format!("pp_dpm_{}", clk_type.name()));
+        let s = fs::read_to_string(&path).ok()?;
+
+        Self::from_str(&s, clk_type, path)
+    }
+
+    pub fn contains(&self, index: u32) -> bool {
+        self.levels.iter().any(|(i, _)| *i == index)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerProfileTableLayout {
+    /// One row per profile (Polaris, Vega10)
+    /// ```text
+    /// NUM        MODE_NAME     SCLK_UP_HYST   SCLK_DOWN_HYST SCLK_ACTIVE_LEVEL ...
+    ///   0   BOOTUP_DEFAULT:        -                -               -        ...
+    ///   6           CUSTOM:        0                0               0        ...
+    /// ```
+    Row,
+    /// One row per clock type for each profile (Vega20, RDNA 1/2)
+    /// ```text
+    /// PROFILE_INDEX(NAME) CLOCK_TYPE(NAME) FPS MinFreqType MinActiveFreqType ...
+    ///  0 BOOTUP_DEFAULT*:
+    ///                     0(       GFXCLK)       0       5       1 ...
+    ///                     1(       SOCCLK)       0       5       1 ...
+    /// ```
+    ClockTypeRows,
+    /// One column per profile (RDNA 3/4), the rows of each clock type share a prefix
+    /// ```text
+    ///                               0 BOOTUP_DEFAULT  1 3D_FULL_SCREEN* ...
+    /// Gfx_FPS                       0                 1                 ...
+    /// Fclk_FPS                      0                 1                 ...
+    /// ```
+    Column,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerProfileHeuristics {
+    pub index: u32,
+    pub name: String,
+    pub active: bool,
+    pub rows: Vec<Vec<String>>, // "-" if not available
+}
+
+/// Heuristics table in `pp_power_profile_mode`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerProfileTable {
+    pub layout: PowerProfileTableLayout,
+    pub columns: Vec<String>,
+    pub row_names: Vec<String>, // clock types for `ClockTypeRows`
+    pub profiles: Vec<PowerProfileHeuristics>,
+}
+
+impl PowerProfileTable {
+    pub fn from_str(s: &str) -> Option<Self> {
+        if s.contains(':') {
+            Self::from_str_rows(s)
+        } else {
+            Self::from_str_columns(s)
+        }
+    }
+
+    fn from_str_rows(s: &str) -> Option<Self> {
+        let mut lines = s.lines();
+        let columns: Vec<String> = lines.next()?.split_whitespace().skip(2).map(|v| v.to_string()).collect();
+        let mut row_names: Vec<String> = Vec::new();
+        let mut profiles: Vec<PowerProfileHeuristics> = Vec::new();
+
+        for l in lines {
+            if let Some((profile, values)) = l.split_once(':') {
+                let mut split = profile.split_whitespace();
+                let index = split.next()?.parse().ok()?;
+                let name = split.next()?.trim_end_matches('*').to_string();
+                let active = profile.contains('*');
+                let values: Vec<String> = values.split_whitespace().map(|v| v.to_string()).collect();
+                let rows = if values.is_empty() { Vec::new() } else { vec![values] };
+
+                profiles.push(PowerProfileHeuristics { index, name, active, rows });
+            } else if let Some((clock_type, values)) = l.split_once(')') {
+                // "0(       GFXCLK)       0       5 ..."
+                let clock_type = clock_type.split_once('(')?.1.trim();
+
+                if profiles.len() == 1 {
+                    row_names.push(clock_type.to_string());
+                }
+
+                profiles.last_mut()?.rows.push(values.split_whitespace().map(|v| v.to_string()).collect());
+            }
+        }
+
+        if profiles.is_empty() {
+            return None;
+        }
+
+        let layout = if row_names.is_empty() {
+            PowerProfileTableLayout::Row
+        } else {
+            PowerProfileTableLayout::ClockTypeRows
+        };
+
+        Some(Self { layout, columns, row_names, profiles })
+    }
+
+    fn from_str_columns(s: &str) -> Option<Self> {
+        let mut lines = s.lines();
+        let mut profiles: Vec<PowerProfileHeuristics> = Vec::new();
+
+        {
+            let mut split = lines.next()?.split_whitespace().peekable();
+
+            while let Some(index) = split.next() {
+                let index = index.parse().ok()?;
+                let name = split.next()?;
+                let active = name.ends_with('*') || split.next_if_eq(&"*").is_some();
+                let name = name.trim_end_matches('*').to_string();
+
+                profiles.push(PowerProfileHeuristics { index, name, active, rows: vec![Vec::new()] });
+            }
+        }
+
+        // Some APUs only list the profile names, one per line.
+        if profiles.len() < 2 {
+            return None;
+        }
+
+        let mut columns: Vec<String> = Vec::new();
+
+        for l in lines {
+            let split: Vec<&str> = l.split_whitespace().collect();
+            let Some(pos) = split.len().checked_sub(profiles.len()) else { continue };
+
+            if pos == 0 {
+                continue;
+            }
+
+            columns.push(split[..pos].join(" "));
+
+            for (profile, v) in profiles.iter_mut().zip(&split[pos..]) {
+                profile.rows[0].push(v.to_string());
+            }
+        }
+
+        Some(Self {
+            layout: PowerProfileTableLayout::Column,
+            columns,
+            row_names: Vec::new(),
+            profiles,
+        })
+    }
+
+    pub fn from_sysfs_path<P: Into<PathBuf>>(path: P) -> Option<Self> {
+        let s = fs::read_to_string(path.into().join("pp_power_profile_mode")).ok()?;
+
         Self::from_str(&s)
     }
+
+    pub fn custom_profile(&self) -> Option<&PowerProfileHeuristics> {
+        self.profiles.iter().find(|p| p.name == "CUSTOM")
+    }
+
+    /// Commands to write `rows` (as shown in the table) to the profile `index`.
+    /// The input of `pp_power_profile_mode` differs by ASIC family:
+    ///  * SMU7 (Polaris): `<profile> <update sclk> <3 sclk values> <update mclk> <3 mclk values>`
+    ///  * Vega10: `<profile> <values>`
+    ///  * Vega20, RDNA 1/2: `<profile> <clock type> <values>` for each clock type row
+    ///  * RDNA 3/4: `<profile> <clock type> <values>` for each clock type in the column
+    pub fn custom_profile_cmds(&self, index: u32, rows: &[Vec<String>]) -> Vec<String> {
+        match self.layout {
+            PowerProfileTableLayout::Row if self.columns.first().is_some_and(|c| c == "SCLK_UP_HYST") => {
+                rows.iter().map(|values| {
+                    let (sclk, mclk) = values.split_at(values.len().min(3));
+
+                    format!("{index} 1 {} 1 {}", sclk.join(" "), mclk.join(" "))
+                }).collect()
+            },
+            PowerProfileTableLayout::Row => {
+                rows.iter().map(|values| format!("{index} {}", values.join(" "))).collect()
+            },
+            PowerProfileTableLayout::ClockTypeRows => {
+                rows.iter().enumerate().map(|(i, values)| format!("{index} {i} {}", values.join(" "))).collect()
+            },
+            PowerProfileTableLayout::Column => {
+                // "Gfx_FPS", "Fclk_FPS" -> "Gfx", "Fclk"
+                let clock_type = |column: &String| column.split_once('_').map_or(column.clone(), |(c, _)| c.to_string());
+                let Some(values) = rows.first() else { return Vec::new() };
+                let columns: Vec<(&String, &String)> = self.columns.iter().zip(values).collect();
+
+                columns
+                    .chunk_by(|a, b| clock_type(a.0) == clock_type(b.0))
+                    .enumerate()
+                    .map(|(i, group)| {
+                        let values: Vec<&str> = group.iter().map(|(_, v)| v.as_str()).collect();
+
+                        format!("{index} {i} {}", values.join(" "))
+                    })
+                    .collect()
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -331,6 +979,27 @@ impl AcousticTargetRpmThreshold {
     }
 }
 
+/// Linear interpolation between `points` sorted by temperature
+pub fn interpolate_fan_speed(points: &[(u8, u8)], temp: u32) -> u8 {
+    let Some(first) = points.first() else { return 100 };
+
+    if temp <= first.0 as u32 {
+        return first.1;
+    }
+
+    let temp = temp as i32;
+
+    for w in points.windows(2) {
+        let [(t0, s0), (t1, s1)] = [w[0], w[1]].map(|(t, s)| (t as i32, s as i32));
+
+        if temp <= t1 {
+            return (s0 + (s1 - s0) * (temp - t0) / (t1 - t0)) as u8;
+        }
+    }
+
+    points.last().map(|p| p.1).unwrap_or(100)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FanCurve {
     pub path: PathBuf,
@@ -389,6 +1058,61 @@ impl FanCurve {
         })
     }
 
+    /// Resamples `points` (any number of (temp, fan speed) points) to the points of the firmware,
+    /// clamped to the ranges and with non-decreasing fan speeds.
+    /// If `points` already has the number of points of the firmware, the temperatures are kept
+    /// and only clamped to be non-decreasing.
+    pub fn resample(&self, points: &[(u8, u8)]) -> Vec<(u8, u8)> {
+        let len = self.points.len();
+        let [min_temp, max_temp] = self.temp_range;
+        let [min_speed, max_speed] = self.fan_speed_range;
+        let mut points = points.to_vec();
+
+        points.sort_by_key(|(temp, _)| *temp);
+
+        if points.len() == len {
+            let [mut pre_temp, mut pre_speed] = [min_temp, min_speed];
+
+            return points
+                .into_iter()
+                .map(|(temp, speed)| {
+                    pre_temp = temp.clamp(min_temp, max_temp).max(pre_temp);
+                    pre_speed = speed.clamp(min_speed, max_speed).max(pre_speed);
+
+                    (pre_temp, pre_speed)
+                })
+                .collect();
+        }
+
+        points.dedup_by_key(|(temp, _)| *temp);
+
+        let (Some(first), Some(last)) = (points.first(), points.last()) else { return self.points.clone() };
+        let [mut start, mut end] = [first.0, last.0].map(|temp| temp.clamp(min_temp, max_temp));
+
+        if start == end {
+            [start, end] = [min_temp, max_temp];
+        }
+
+        let mut pre_speed = min_speed;
+
+        (0..len)
+            .map(|i| {
+                let temp = if len > 1 {
+                    start as usize + (end - start) as usize * i / (len - 1)
+                } else {
+                    end as usize
+                } as u8;
+                let speed = interpolate_fan_speed(&points, temp as u32)
+                    .clamp(min_speed, max_speed)
+                    .max(pre_speed);
+
+                pre_speed = speed;
+
+                (temp, speed)
+            })
+            .collect()
+    }
+
 /*
     pub fn is_all_zero_points(&self) -> bool {
         self.points.iter().all(|v| v == &(0, 0))
@@ -1,166 +1,878 @@
-use std::{fs, io::{self, Write}};
+use std::{fmt, fs, io::{self, Write}};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use log::debug;
+use proc_prog_name::ProcProgEntry;
+use serde::{Deserialize, Serialize};
 
 use libdrm_amdgpu_sys::AMDGPU;
 use AMDGPU::{DpmForcedLevel, PowerProfile};
 
-use crate::config::{ParsedConfigEntry, ParsedConfigPerDevice};
-use crate::amdgpu_device::{AmdgpuDevice, FanCurve};
+use crate::config::{DpmLevelMasks, ParsedConfigEntry, ParsedConfigPerDevice};
+use crate::amdgpu_device::{
+    AcousticTargetRpmThreshold,
+    AmdgpuDevice,
+    ClockLimits,
+    ClockVoltageStates,
+    DpmLevels,
+    FanCurve,
+    FanMinPwm,
+    FanTargetTemp,
+    FanZeroRpm,
+    OdPercent,
+    PowerProfileTable,
+    SclkOffset,
+    VddcCurve,
+    VddgfxOffset,
+};
+use crate::fan_control::SoftwareFanControl;
+use crate::journal::Journal;
+use crate::process::{self, GpuBusySampler, ProcTree};
 
 pub struct AppDevice {
     pub amdgpu_device: AmdgpuDevice,
     pub config_device: ParsedConfigPerDevice,
     pub cache_pid: Option<i32>,
+    pub cache_entry: Option<ParsedConfigEntry>, // applied (merged) entry
+    pub applied_entries: Vec<String>, // names of the entries merged into `cache_entry`
+    pub matched_entries: Vec<(ParsedConfigEntry, Vec<i32>)>, // matched entries and their pids (including descendants)
+    pub entry_pids: Vec<Option<Vec<i32>>>, // pids matched by each entry, until the next rescan
+    pub gpu_busy: GpuBusySampler,
+    pub switch_state: SwitchState,
+    pub fan_control: Option<SoftwareFanControl>,
+    pub snapshot: EffectiveConfig, // hardware state at startup, or before the crashed instance
+    pub journal_entries: Vec<String>, // entries applied by the crashed instance
+    pub setting_status: Vec<(&'static str, SettingStatus)>, // result of the last apply
     pub changed_default_config: bool,
 }
 
+/// Result of the last write of a setting, checked by reading the value back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingStatus {
+    Applied, // including already set or unsupported
+    Mismatch { expected: String, actual: String },
+    Failed(String),
+    RolledBack, // written, then returned to the previous value because another setting failed
+    Skipped, // not set in the config, or not reached because a previous setting failed
+}
+
+impl fmt::Display for SettingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Applied => write!(f, "applied"),
+            Self::Mismatch { expected, actual } => write!(f, "mismatch (expected: {expected}, actual: {actual})"),
+            Self::Failed(e) => write!(f, "failed ({e})"),
+            Self::RolledBack => write!(f, "rolled back"),
+            Self::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+impl SettingStatus {
+    fn from_error(e: &io::Error) -> Self {
+        match e.get_ref().and_then(|inner| inner.downcast_ref::<Mismatch>()) {
+            Some(m) => Self::Mismatch { expected: m.expected.clone(), actual: m.actual.clone() },
+            None => Self::Failed(e.to_string()),
+        }
+    }
+}
+
+/// The value read back after the write differs from the written value.
+#[derive(Debug)]
+struct Mismatch {
+    name: String,
+    expected: String,
+    actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} mismatch (expected: {}, actual: {})", self.name, self.expected, self.actual)
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+impl Mismatch {
+    fn check<T: PartialEq + fmt::Debug>(name: &str, expected: &T, actual: Option<T>) -> Option<Self> {
+        if actual.as_ref() == Some(expected) {
+            return None;
+        }
+
+        let mismatch = Self {
+            name: name.to_string(),
+            expected: format!("{expected:?}"),
+            actual: actual.map_or_else(|| "unreadable".to_string(), |v| format!("{v:?}")),
+        };
+
+        debug!("    {mismatch}");
+
+        Some(mismatch)
+    }
+}
+
+/// Calls `attempt` until it succeeds, sleeping with backoff in between.
+fn retry_with_backoff<E>(name: &str, attempt: impl Fn() -> Result<(), E>) -> Result<(), E> {
+    let mut backoff = VERIFY_BACKOFF;
+
+    for i in 1..=VERIFY_RETRIES {
+        if attempt().is_ok() {
+            return Ok(());
+        }
+
+        debug!("    Retry {name} in {backoff:?} ({i}/{VERIFY_RETRIES})");
+        std::thread::sleep(backoff);
+        backoff *= 2;
+    }
+
+    attempt()
+}
+
+/// Edits of `pp_od_clk_voltage` and `gpu_od/fan_ctrl/*` collected for one apply.
+/// The edits of all files are written first, then committed with a single `c`,
+/// because the kernel commits the whole OD table on any `c`.
+/// So the SMU table is uploaded once and no partial state is live in between.
+#[derive(Default)]
+pub struct OdCommitBuilder<'a> {
+    files: Vec<OdFile>,
+    checks: Vec<(Knob, OdCheck<'a>)>,
+}
+
+struct OdFile {
+    path: PathBuf,
+    cmds: Vec<String>,
+    knobs: Vec<Knob>,
+}
+
+type OdCheck<'a> = Box<dyn Fn() -> Option<Mismatch> + 'a>;
+
+impl<'a> OdCommitBuilder<'a> {
+    /// Adds the commands of `knob` for `path`, and `read` to check the committed value.
+    pub fn push<T: PartialEq + fmt::Debug + 'a>(
+        &mut self,
+        knob: Knob,
+        path: &Path,
+        cmds: Vec<String>,
+        expected: T,
+        read: impl Fn() -> Option<T> + 'a,
+    ) {
+        match self.files.iter_mut().find(|file| file.path == path) {
+            Some(file) => {
+                file.cmds.extend(cmds);
+                file.knobs.push(knob);
+            },
+            None => self.files.push(OdFile { path: path.to_path_buf(), cmds, knobs: vec![knob] }),
+        }
+
+        self.checks.push((knob, Box::new(move || Mismatch::check(knob.name(), &expected, read()))));
+    }
+
+    /// Writes the edits of all files, then sends `c` once to the last file.
+    /// Returns the knob of the file that failed.
+    fn commit(&self) -> Result<(), (Knob, io::Error)> {
+        let Some(last) = self.files.last() else { return Ok(()) };
+
+        for file in &self.files {
+            debug!("    Write {:?} ({:?})", file.path, file.cmds);
+            AppDevice::write_cmds(&file.path, &file.cmds).map_err(|e| (file.knobs[0], e))?;
+        }
+
+        debug!("    Commit {:?}", last.path);
+
+        AppDevice::write_cmds(&last.path, &["c".to_string()]).map_err(|e| (last.knobs[0], e))
+    }
+
+    /// Commits all edits and reads every knob back, retrying with backoff on a mismatch.
+    /// Returns the first knob that failed.
+    pub fn commit_verified(&self) -> Result<(), (Knob, io::Error)> {
+        if self.files.is_empty() {
+            return Ok(());
+        }
+
+        retry_with_backoff("OD commit", || {
+            self.commit()?;
+
+            match self.checks.iter().find_map(|(knob, check)| check().map(|m| (*knob, m))) {
+                Some((knob, mismatch)) => Err((knob, io::Error::other(mismatch))),
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+/// Knobs in the order they are written.
+/// OD knobs are kept together, so they are committed at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Knob {
+    PerfLevel,
+    CustomProfile,
+    PowerProfile,
+    PowerCap,
+    FanZeroRpm,
+    FanTargetTemp,
+    FanMinimumPwm,
+    FanTargetRpm,
+    SclkOffset,
+    VddgfxOffset,
+    SclkLimits,
+    MclkLimits,
+    VddcCurve,
+    SclkStates,
+    MclkStates,
+    FanCurve,
+    SclkOd,
+    MclkOd,
+    DpmLevels,
+}
+
+impl Knob {
+    pub const ALL: [Self; 19] = [
+        Self::PerfLevel,
+        Self::CustomProfile,
+        Self::PowerProfile,
+        Self::PowerCap,
+        Self::FanZeroRpm,
+        Self::FanTargetTemp,
+        Self::FanMinimumPwm,
+        Self::FanTargetRpm,
+        Self::SclkOffset,
+        Self::VddgfxOffset,
+        Self::SclkLimits,
+        Self::MclkLimits,
+        Self::VddcCurve,
+        Self::SclkStates,
+        Self::MclkStates,
+        Self::FanCurve,
+        Self::SclkOd,
+        Self::MclkOd,
+        Self::DpmLevels,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PerfLevel => "perf_level",
+            Self::CustomProfile => "custom_profile",
+            Self::PowerProfile => "power_profile",
+            Self::PowerCap => "power_cap",
+            Self::FanZeroRpm => "fan_zero_rpm",
+            Self::FanTargetTemp => "fan_target_temp",
+            Self::FanMinimumPwm => "fan_minimum_pwm",
+            Self::FanTargetRpm => "fan_target_rpm",
+            Self::SclkOffset => "sclk_offset",
+            Self::VddgfxOffset => "vddgfx_offset",
+            Self::SclkLimits => "sclk_limits",
+            Self::MclkLimits => "mclk_limits",
+            Self::VddcCurve => "vddc_curve",
+            Self::SclkStates => "sclk_states",
+            Self::MclkStates => "mclk_states",
+            Self::FanCurve => "fan_curve",
+            Self::SclkOd => "sclk_od",
+            Self::MclkOd => "mclk_od",
+            Self::DpmLevels => "dpm_levels",
+        }
+    }
+
+    /// Whether `config` writes the knob (unset knobs are left as they are)
+    pub fn is_written(&self, config: &EffectiveConfig) -> bool {
+        match self {
+            Self::PerfLevel | Self::PowerProfile => true,
+            Self::CustomProfile => config.custom_profile.is_some() && config.profile == PowerProfile::CUSTOM,
+            Self::PowerCap => config.power_cap_watt.is_some(),
+            Self::FanZeroRpm => config.fan_zero_rpm.is_some(),
+            Self::FanTargetTemp => config.fan_target_temperature.is_some(),
+            Self::FanMinimumPwm => config.fan_minimum_pwm.is_some(),
+            Self::FanTargetRpm => config.acoustic_target_rpm_threshold.is_some(),
+            Self::SclkOffset => config.sclk_offset.is_some(),
+            Self::VddgfxOffset => config.vddgfx_offset.is_some(),
+            Self::SclkLimits => config.min_sclk.is_some() || config.max_sclk.is_some(),
+            Self::MclkLimits => config.min_mclk.is_some() || config.max_mclk.is_some(),
+            Self::VddcCurve => config.vddc_curve.is_some(),
+            Self::SclkStates => config.sclk_states.is_some(),
+            Self::MclkStates => config.mclk_states.is_some(),
+            Self::FanCurve => config.fan_curve_points.as_deref().is_some_and(AppDevice::edited_fan_curve),
+            Self::SclkOd => config.sclk_od.is_some(),
+            Self::MclkOd => config.mclk_od.is_some(),
+            Self::DpmLevels => !config.dpm_levels.is_empty() || config.perf_level == DpmForcedLevel::Manual,
+        }
+    }
+
+    /// Knobs written to `pp_od_clk_voltage` or `gpu_od/fan_ctrl/*`, which need a commit
+    pub fn is_od(&self) -> bool {
+        matches!(
+            self,
+            Self::FanZeroRpm
+                | Self::FanTargetTemp
+                | Self::FanMinimumPwm
+                | Self::FanTargetRpm
+                | Self::SclkOffset
+                | Self::VddgfxOffset
+                | Self::SclkLimits
+                | Self::MclkLimits
+                | Self::VddcCurve
+                | Self::SclkStates
+                | Self::MclkStates
+                | Self::FanCurve
+        )
+    }
+}
+
+/// Result of returning the knobs to the previous values after a failed write
+#[derive(Debug)]
+pub struct Rollback {
+    pub failed: Vec<(&'static str, io::Error)>,
+    pub not_restorable: Vec<&'static str>, // the previous values could not be read (e.g. DPM level masks)
+}
+
+/// The knob that failed to be written, and the rollback of the transaction
+#[derive(Debug)]
+pub struct ApplyError {
+    pub knob: &'static str,
+    pub error: io::Error,
+    pub rollback: Rollback,
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to set {} ({:?}), rollback: ", self.knob, self.error)?;
+
+        if self.rollback.failed.is_empty() && self.rollback.not_restorable.is_empty() {
+            return write!(f, "done");
+        }
+
+        if !self.rollback.failed.is_empty() {
+            write!(f, "failed {:?} ", self.rollback.failed)?;
+        }
+
+        if !self.rollback.not_restorable.is_empty() {
+            write!(f, "not restorable {:?}", self.rollback.not_restorable)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SwitchState {
+    pub switched_at: Option<Instant>,
+    pub pending: Option<(ParsedConfigEntry, Instant)>, // detected entry waiting for `apply_delay_secs`
+    pub lost_since: Option<Instant>, // since when the applied entry is no longer detected
+}
+
+/// Settings applied to the device as one unit:
+/// the defaults of the device with the overrides of the active entry on top.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EffectiveConfig {
+    #[serde(with = "crate::journal::serde_perf_level")]
+    pub perf_level: DpmForcedLevel,
+    #[serde(with = "crate::journal::serde_power_profile")]
+    pub profile: PowerProfile,
+    pub custom_profile: Option<Vec<Vec<i64>>>,
+    pub power_cap_watt: Option<u32>,
+    pub fan_target_temperature: Option<u32>,
+    pub fan_minimum_pwm: Option<u32>,
+    pub acoustic_target_rpm_threshold: Option<u32>,
+    pub sclk_offset: Option<i32>,
+    pub vddgfx_offset: Option<i32>,
+    pub min_sclk: Option<u32>,
+    pub max_sclk: Option<u32>,
+    pub min_mclk: Option<u32>,
+    pub max_mclk: Option<u32>,
+    pub vddc_curve: Option<Vec<(u32, u32)>>,
+    pub sclk_states: Option<Vec<(u32, u32)>>,
+    pub mclk_states: Option<Vec<(u32, u32)>>,
+    pub sclk_od: Option<u32>,
+    pub mclk_od: Option<u32>,
+    pub dpm_levels: DpmLevelMasks,
+    pub fan_zero_rpm: Option<bool>,
+    pub fan_curve_points: Option<Vec<(u8, u8)>>,
+}
+
+impl EffectiveConfig {
+    /// Unspecified defaults fall back to `snapshot`,
+    /// the state at startup or before the crashed instance.
+    pub fn from_defaults(config_device: &ParsedConfigPerDevice, snapshot: &EffectiveConfig) -> Self {
+        let config = Self {
+            perf_level: config_device.default_perf_level,
+            profile: config_device.default_profile,
+            custom_profile: config_device.custom_profile.clone(),
+            power_cap_watt: config_device.default_power_cap_watt.or(snapshot.power_cap_watt),
+            fan_target_temperature: config_device.default_fan_target_temperature
+                .or(snapshot.fan_target_temperature),
+            fan_minimum_pwm: config_device.default_fan_minimum_pwm.or(snapshot.fan_minimum_pwm),
+            acoustic_target_rpm_threshold: config_device.acoustic_target_rpm_threshold
+                .or(snapshot.acoustic_target_rpm_threshold),
+            sclk_offset: config_device.sclk_offset.or(snapshot.sclk_offset),
+            vddgfx_offset: config_device.vddgfx_offset.or(snapshot.vddgfx_offset),
+            min_sclk: config_device.min_sclk.or(snapshot.min_sclk),
+            max_sclk: config_device.max_sclk.or(snapshot.max_sclk),
+            min_mclk: config_device.min_mclk.or(snapshot.min_mclk),
+            max_mclk: config_device.max_mclk.or(snapshot.max_mclk),
+            vddc_curve: config_device.vddc_curve.clone().or_else(|| snapshot.vddc_curve.clone()),
+            sclk_states: config_device.sclk_states.clone().or_else(|| snapshot.sclk_states.clone()),
+            mclk_states: config_device.mclk_states.clone().or_else(|| snapshot.mclk_states.clone()),
+            sclk_od: config_device.sclk_od.or(snapshot.sclk_od),
+            mclk_od: config_device.mclk_od.or(snapshot.mclk_od),
+            dpm_levels: config_device.dpm_levels.clone(),
+            fan_zero_rpm: config_device.fan_zero_rpm.or(snapshot.fan_zero_rpm),
+            fan_curve_points: config_device.fan_curve_points.clone()
+                .or_else(|| snapshot.fan_curve_points.clone()),
+        };
+
+        config.with_manual_perf_level_for_dpm_levels()
+    }
+
+    /// The current values of every knob, read before any settings are applied.
+    pub fn snapshot(amdgpu_device: &AmdgpuDevice) -> Self {
+        let sysfs_path = &amdgpu_device.sysfs_path;
+        let custom_profile = read_custom_profile(sysfs_path);
+
+        Self {
+            perf_level: DpmForcedLevel::get_from_sysfs(sysfs_path).unwrap_or(DpmForcedLevel::Auto),
+            profile: PowerProfile::get_current_profile_from_sysfs(sysfs_path)
+                .unwrap_or(PowerProfile::BOOTUP_DEFAULT),
+            custom_profile,
+            power_cap_watt: amdgpu_device.power_cap.as_ref().map(|cap| cap.current),
+            fan_target_temperature: amdgpu_device.fan_target_temperature.as_ref().map(|fan| fan.target_temp),
+            fan_minimum_pwm: amdgpu_device.fan_minimum_pwm.as_ref().map(|fan| fan.minimum_pwm),
+            acoustic_target_rpm_threshold: amdgpu_device.acoustic_target_rpm_threshold.as_ref().map(|ac| ac.rpm),
+            sclk_offset: amdgpu_device.sclk_offset.as_ref().map(|sclk| sclk.current),
+            vddgfx_offset: amdgpu_device.vddgfx_offset.as_ref().map(|vddgfx| vddgfx.current),
+            min_sclk: amdgpu_device.sclk_limits.as_ref().and_then(|sclk| sclk.min),
+            max_sclk: amdgpu_device.sclk_limits.as_ref().and_then(|sclk| sclk.max),
+            min_mclk: amdgpu_device.mclk_limits.as_ref().and_then(|mclk| mclk.min),
+            max_mclk: amdgpu_device.mclk_limits.as_ref().and_then(|mclk| mclk.max),
+            vddc_curve: amdgpu_device.vddc_curve.as_ref().map(|curve| curve.points.clone()),
+            sclk_states: amdgpu_device.sclk_states.as_ref().map(|sclk| sclk.states.clone()),
+            mclk_states: amdgpu_device.mclk_states.as_ref().map(|mclk| mclk.states.clone()),
+            sclk_od: amdgpu_device.sclk_od.as_ref().map(|od| od.current),
+            mclk_od: amdgpu_device.mclk_od.as_ref().map(|od| od.current),
+            dpm_levels: DpmLevelMasks::default(),
+            fan_zero_rpm: amdgpu_device.fan_zero_rpm.as_ref().map(|f| f.flag),
+            fan_curve_points: amdgpu_device.fan_curve.as_ref().map(|fan_curve| fan_curve.points.clone()),
+        }
+    }
+
+    pub fn with_entry(mut self, entry: &ParsedConfigEntry) -> Self {
+        self.perf_level = entry.perf_level.unwrap_or(self.perf_level);
+        self.profile = entry.profile.unwrap_or(self.profile);
+        self.custom_profile = entry.custom_profile.clone().or(self.custom_profile);
+        self.power_cap_watt = entry.power_cap_watt.or(self.power_cap_watt);
+        self.fan_target_temperature = entry.fan_target_temperature.or(self.fan_target_temperature);
+        self.fan_minimum_pwm = entry.fan_minimum_pwm.or(self.fan_minimum_pwm);
+        self.acoustic_target_rpm_threshold = entry.acoustic_target_rpm_threshold
+            .or(self.acoustic_target_rpm_threshold);
+        self.sclk_offset = entry.sclk_offset.or(self.sclk_offset);
+        self.vddgfx_offset = entry.vddgfx_offset.or(self.vddgfx_offset);
+        self.min_sclk = entry.min_sclk.or(self.min_sclk);
+        self.max_sclk = entry.max_sclk.or(self.max_sclk);
+        self.min_mclk = entry.min_mclk.or(self.min_mclk);
+        self.max_mclk = entry.max_mclk.or(self.max_mclk);
+        self.vddc_curve = entry.vddc_curve.clone().or(self.vddc_curve);
+        self.sclk_states = entry.sclk_states.clone().or(self.sclk_states);
+        self.mclk_states = entry.mclk_states.clone().or(self.mclk_states);
+        self.sclk_od = entry.sclk_od.or(self.sclk_od);
+        self.mclk_od = entry.mclk_od.or(self.mclk_od);
+        self.dpm_levels = entry.dpm_levels.clone().or(&self.dpm_levels);
+        self.fan_zero_rpm = entry.fan_zero_rpm.or(self.fan_zero_rpm);
+        self.fan_curve_points = entry.fan_curve_points.clone().or(self.fan_curve_points);
+
+        self.with_manual_perf_level_for_dpm_levels()
+    }
+
+    /// DPM level masks only take effect with the `manual` perf level.
+    fn with_manual_perf_level_for_dpm_levels(mut self) -> Self {
+        if !self.dpm_levels.is_empty() {
+            self.perf_level = DpmForcedLevel::Manual;
+        }
+
+        self
+    }
+}
+
+/// Heuristics of the CUSTOM profile in `pp_power_profile_mode`
+fn read_custom_profile(sysfs_path: &Path) -> Option<Vec<Vec<i64>>> {
+    let table = PowerProfileTable::from_sysfs_path(sysfs_path)?;
+
+    table.custom_profile()?.rows
+        .iter()
+        .map(|row| row.iter().map(|v| v.parse::<i64>().ok()).collect::<Option<Vec<_>>>())
+        .collect::<Option<Vec<_>>>()
+}
+
+pub enum Switch {
+    Keep,
+    Apply(Box<ParsedConfigEntry>),
+    Restore,
+}
+
 const IO_ERROR_POWER_CAP: &str = "Can't get the power cap";
+const VERIFY_RETRIES: u32 = 3;
+const VERIFY_BACKOFF: Duration = Duration::from_millis(10);
 
 impl AppDevice {
-    pub fn set_perf_level(&self, perf_level: DpmForcedLevel) -> io::Result<()> {
-        let perf_level = perf_level.to_arg();
-        fs::write(&self.amdgpu_device.dpm_perf_level_path, perf_level)
+    pub fn new(amdgpu_device: AmdgpuDevice, config_device: ParsedConfigPerDevice) -> Self {
+        let fan_control = amdgpu_device.hwmon_pwm.clone().map(SoftwareFanControl::new);
+        let (snapshot, journal_entries) = match Journal::load(&amdgpu_device.pci_bus) {
+            // The previous instance exited without restoring the device,
+            // so the current values are not the original state.
+            Some(journal) => {
+                debug!(
+                    "{} ({}): Found the journal of the previous instance (pid: {}, entries: {:?})",
+                    amdgpu_device.pci_bus,
+                    amdgpu_device.device_name,
+                    journal.pid,
+                    journal.applied_entries,
+                );
+                (journal.snapshot, journal.applied_entries)
+            },
+            None => (EffectiveConfig::snapshot(&amdgpu_device), Vec::new()),
+        };
+
+        Self {
+            amdgpu_device,
+            config_device,
+            cache_pid: None,
+            cache_entry: None,
+            applied_entries: Vec::new(),
+            matched_entries: Vec::new(),
+            entry_pids: Vec::new(),
+            gpu_busy: GpuBusySampler::default(),
+            switch_state: SwitchState::default(),
+            fan_control,
+            snapshot,
+            journal_entries,
+            setting_status: Vec::new(),
+            changed_default_config: false,
+        }
     }
 
-    pub fn set_default_perf_level(&self) -> io::Result<()> {
-        let perf_level = self.config_device.default_perf_level;
+    pub fn clear_entry_pids(&mut self) {
+        self.entry_pids.clear();
+    }
+
+    pub fn clear_cache(&mut self) {
+        self.cache_pid = None;
+        self.cache_entry = None;
+        self.applied_entries.clear();
+        self.matched_entries.clear();
+        self.switch_state.pending = None;
+        self.switch_state.lost_since = None;
+    }
+
+    /// Updates the software fan control with the curve of the applied entry or the device.
+    pub fn update_fan_control(&mut self) {
+        let Some(ref mut fan_control) = self.fan_control else { return };
+        let curve = self.cache_entry
+            .as_ref()
+            .and_then(|e| e.software_fan_curve.as_ref())
+            .or(self.config_device.software_fan_curve.as_ref());
+
+        fan_control.set_curve(curve);
+        fan_control.tick();
+    }
+
+    /// Decides whether to switch to `target` (`None` means the default settings),
+    /// considering `apply_delay_secs`, `restore_grace_secs` and `min_dwell_secs`.
+    pub fn next_switch(&mut self, target: Option<ParsedConfigEntry>) -> Switch {
+        let now = Instant::now();
+        let state = &mut self.switch_state;
+
+        if target == self.cache_entry {
+            state.pending = None;
+            state.lost_since = None;
+            return Switch::Keep;
+        }
+
+        let lost_since = *state.lost_since.get_or_insert(now);
+        let grace = self.cache_entry.as_ref().and_then(|e| e.restore_grace_secs).unwrap_or(0);
+        let released = now.duration_since(lost_since) >= Duration::from_secs(grace);
+
+        let ready = if let Some(ref target) = target {
+            let since = match state.pending {
+                Some((ref e, since)) if e == target => since,
+                _ => {
+                    state.pending = Some((target.clone(), now));
+                    now
+                },
+            };
+            let delay = target.apply_delay_secs.unwrap_or(0);
+
+            now.duration_since(since) >= Duration::from_secs(delay)
+        } else {
+            state.pending = None;
+            true
+        };
+
+        let dwell = Duration::from_secs(self.config_device.min_dwell_secs);
+        let dwelled = state.switched_at.is_none_or(|t| now.duration_since(t) >= dwell);
+
+        if !(released && ready && dwelled) {
+            return Switch::Keep;
+        }
+
+        state.switched_at = Some(now);
+        state.pending = None;
+        state.lost_since = None;
+
+        match target {
+            Some(target) => Switch::Apply(Box::new(target)),
+            None => Switch::Restore,
+        }
+    }
+
+    /// Returns the matched entries in priority order.
+    /// Entries after the first one are returned only if all of them allow merging.
+    pub fn detect_entries(
+        &mut self,
+        procs: &[ProcProgEntry],
+        proc_tree: &ProcTree,
+    ) -> Vec<(ParsedConfigEntry, Vec<i32>)> {
+        let mut matched: Vec<(ParsedConfigEntry, Vec<i32>)> = Vec::new();
+        let pci_bus = self.amdgpu_device.pci_bus;
+
+        self.gpu_busy.next_tick();
+
+        // Matching reads `/proc/<pid>/{cgroup,environ,status,cmdline}`,
+        // so the results are kept until the process list is rescanned (`clear_entry_pids`).
+        if self.entry_pids.len() != self.config_device.entries.len() {
+            self.entry_pids = vec![None; self.config_device.entries.len()];
+        }
+
+        for (e, entry_pids) in self.config_device.entries.iter().zip(self.entry_pids.iter_mut()) {
+            if !matched.is_empty() && !e.merge {
+                continue;
+            }
+
+            let mut pids: Vec<i32> = entry_pids
+                .get_or_insert_with(|| procs.iter().filter(|p| e.is_match(p)).map(|p| p.pid).collect())
+                .clone();
+
+            if e.include_descendants {
+                if let Some((_, tracked_pids)) = self.matched_entries.iter().find(|(m, _)| m == e) {
+                    // Keep following the descendants even after their launcher has exited.
+                    pids.extend(tracked_pids.iter().filter(|pid| proc_tree.is_alive(**pid)));
+                    pids.sort_unstable();
+                    pids.dedup();
+                }
+
+                pids = proc_tree.with_descendants(&pids);
+            }
+
+            if e.require_device_usage && !pids.iter().any(|pid| process::uses_drm_device(*pid, &pci_bus)) {
+                continue;
+            }
+
+            if let Some(min_busy) = e.min_gfx_busy_percent && !pids.is_empty() {
+                let busy: f64 = pids
+                    .iter()
+                    .filter_map(|pid| self.gpu_busy.busy_percent(*pid, &pci_bus))
+                    .sum();
+
+                if busy < min_busy as f64 {
+                    continue;
+                }
+            }
+
+            if pids.is_empty() {
+                continue;
+            }
+
+            matched.push((e.clone(), pids));
+
+            if !e.merge {
+                break;
+            }
+        }
+
+        matched
+    }
+
+    pub fn set_perf_level(&self, perf_level: DpmForcedLevel) -> io::Result<()> {
         let current_perf_level = DpmForcedLevel::get_from_sysfs(&self.amdgpu_device.sysfs_path)?;
 
         if current_perf_level != perf_level {
-            debug!("    Set default perf_level ({perf_level:?})");
-            fs::write(&self.amdgpu_device.dpm_perf_level_path, perf_level.to_arg())
+            debug!("    Set perf_level ({perf_level:?})");
+            Self::write_verified(
+                "perf_level",
+                &perf_level,
+                || fs::write(&self.amdgpu_device.dpm_perf_level_path, perf_level.to_arg()),
+                || DpmForcedLevel::get_from_sysfs(&self.amdgpu_device.sysfs_path).ok(),
+            )
         } else {
             Ok(())
         }
     }
 
     pub fn set_power_profile(&self, profile: PowerProfile) -> io::Result<()> {
-        let profile = (profile as u32).to_string();
-        fs::write(&self.amdgpu_device.power_profile_path, profile)
-    }
-
-    pub fn set_default_power_profile(&self) -> io::Result<()> {
-        let profile = self.config_device.default_profile;
         let Some(current_profile) = PowerProfile::get_current_profile_from_sysfs(&self.amdgpu_device.sysfs_path)
             else { return Err(io::Error::last_os_error()) };
 
         if current_profile != profile {
-            let profile = (profile as u32).to_string();
-            debug!("    Set default profile ({profile:?})");
-            fs::write(&self.amdgpu_device.power_profile_path, profile)
+            debug!("    Set profile ({profile:?})");
+            Self::write_verified(
+                "power_profile",
+                &profile,
+                || fs::write(&self.amdgpu_device.power_profile_path, (profile as u32).to_string()),
+                || PowerProfile::get_current_profile_from_sysfs(&self.amdgpu_device.sysfs_path),
+            )
         } else {
             Ok(())
         }
     }
 
-    pub fn set_power_cap(&self, power_cap_watt: u32) -> io::Result<()> {
-        let Some(current_power_cap_watt) = fs::read_to_string(&self.amdgpu_device.power_cap_path)
+    /// Writes the heuristics of the CUSTOM profile to `pp_power_profile_mode`,
+    /// which also selects the CUSTOM profile.
+    pub fn set_custom_profile(&self, custom_profile: &[Vec<i64>]) -> io::Result<()> {
+        let Some(table) = PowerProfileTable::from_sysfs_path(&self.amdgpu_device.sysfs_path)
+            else { return Ok(()) };
+        let Some(current) = table.custom_profile() else {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "CUSTOM profile is not supported"));
+        };
+
+        if current.rows.len() < custom_profile.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("custom_profile has {} rows, but the device has {} rows", custom_profile.len(), current.rows.len()),
+            ));
+        }
+
+        for (i, (row, current_row)) in custom_profile.iter().zip(&current.rows).enumerate() {
+            if row.len() != current_row.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("custom_profile row {i} has {} values, but the device has {} columns", row.len(), current_row.len()),
+                ));
+            }
+        }
+
+        let expected: Vec<Vec<String>> = custom_profile
+            .iter()
+            .map(|row| row.iter().map(|v| v.to_string()).collect())
+            .collect();
+        let read_rows = || -> Option<Vec<Vec<String>>> {
+            let table = PowerProfileTable::from_sysfs_path(&self.amdgpu_device.sysfs_path)?;
+            let rows = table.custom_profile()?.rows.iter().take(expected.len()).cloned().collect();
+
+            Some(rows)
+        };
+
+        if current.active && read_rows().as_ref() == Some(&expected) {
+            return Ok(());
+        }
+
+        debug!("    Set custom_profile ({custom_profile:?})");
+
+        let write = || -> io::Result<()> {
+            let mut file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.amdgpu_device.power_profile_path)?;
+
+            for cmd in table.custom_profile_cmds(current.index, &expected) {
+                file.write_all(cmd.as_bytes())?;
+            }
+
+            Ok(())
+        };
+
+        Self::write_verified("custom_profile", &expected, write, read_rows)
+    }
+
+    fn read_power_cap_watt(&self) -> Option<u32> {
+        fs::read_to_string(&self.amdgpu_device.power_cap_path)
             .ok()
             .and_then(|s| s.trim_end().parse::<u32>().ok())
             .and_then(|v| v.checked_div(1_000_000))
+    }
+
+    pub fn set_power_cap(&self, power_cap_watt: u32) -> io::Result<()> {
+        let Some(current_power_cap_watt) = self.read_power_cap_watt()
             else { return Err(io::Error::other(IO_ERROR_POWER_CAP)) };
 
         if power_cap_watt != current_power_cap_watt {
+            debug!("    Set power cap ({power_cap_watt}W)");
             let power_cap = (power_cap_watt * 1_000_000).to_string();
-            fs::write(&self.amdgpu_device.power_cap_path, power_cap)
+            Self::write_verified(
+                "power_cap",
+                &power_cap_watt,
+                || fs::write(&self.amdgpu_device.power_cap_path, &power_cap),
+                || self.read_power_cap_watt(),
+            )
         } else {
             Ok(())
         }
     }
 
-    pub fn set_default_power_cap(&self) -> io::Result<()> {
-        let Some(target_power_cap_watt) = self.config_device.default_power_cap_watt
-            else { return Ok(()) };
-
-        debug!("    Set default power cap. ({target_power_cap_watt}W)");
-
-        self.set_power_cap(target_power_cap_watt)
-    }
-
-    pub fn set_fan_target_temp(&self, target_temp: u32) -> io::Result<()> {
+    pub fn stage_fan_target_temp<'a>(&'a self, od: &mut OdCommitBuilder<'a>, target_temp: u32) -> io::Result<()> {
         let Some(ref fan_target_temp) = self.amdgpu_device.fan_target_temperature
             else { return Ok(()) };
-        let mut file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&fan_target_temp.path)?;
-        let target_temp = format!("{target_temp} ");
-        file.write_all(target_temp.as_bytes())?;
-        Self::commit(&mut file)
-    }
+        let read = || FanTargetTemp::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.target_temp);
 
-    pub fn set_default_fan_target_temp(&self) -> io::Result<()> {
-        let Some(target_temp) = self.config_device.default_fan_target_temperature
-            else { return Ok(()) };
+        if read() == Some(target_temp) {
+            return Ok(());
+        }
+
+        debug!("    Set fan_target_temperature ({target_temp}C)");
 
-        debug!("    Set default fan_target_temperature ({target_temp}C)");
+        od.push(Knob::FanTargetTemp, &fan_target_temp.path, vec![format!("{target_temp} ")], target_temp, read);
 
-        self.set_fan_target_temp(target_temp)
+        Ok(())
     }
 
-    pub fn set_fan_minimum_pwm(&self, minimum_pwm: u32) -> io::Result<()> {
+    pub fn stage_fan_minimum_pwm<'a>(&'a self, od: &mut OdCommitBuilder<'a>, minimum_pwm: u32) -> io::Result<()> {
         let Some(ref fan_minimum_pwm) = self.amdgpu_device.fan_minimum_pwm
             else { return Ok(()) };
-        let mut file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&fan_minimum_pwm.path)?;
-        let minimum_pwm = format!("{minimum_pwm} ");
-        file.write_all(minimum_pwm.as_bytes())?;
-        Self::commit(&mut file)
-    }
+        let read = || FanMinPwm::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.minimum_pwm);
 
-    pub fn set_default_fan_minimum_pwm(&self) -> io::Result<()> {
-        let Some(minimum_pwm) = self.config_device.default_fan_minimum_pwm
-            else { return Ok(()) };
+        if read() == Some(minimum_pwm) {
+            return Ok(());
+        }
+
+        debug!("    Set fan_minimum_pwm ({minimum_pwm}%)");
 
-        debug!("    Set default fan_minimum_pwm ({minimum_pwm}%)");
+        od.push(Knob::FanMinimumPwm, &fan_minimum_pwm.path, vec![format!("{minimum_pwm} ")], minimum_pwm, read);
 
-        self.set_fan_minimum_pwm(minimum_pwm)
+        Ok(())
     }
 
-    pub fn set_fan_zero_rpm(&self) -> io::Result<()> {
-        let Some(fan_zero_rpm) = self.config_device.fan_zero_rpm
-            else { return Ok(()) };
+    pub fn stage_fan_zero_rpm<'a>(&'a self, od: &mut OdCommitBuilder<'a>, fan_zero_rpm: bool) -> io::Result<()> {
+        let Some(ref f) = self.amdgpu_device.fan_zero_rpm else { return Ok(()) };
+        let read = || FanZeroRpm::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.flag);
+
+        if read() == Some(fan_zero_rpm) {
+            return Ok(());
+        }
 
         debug!("    Set fan_zero_rpm ({fan_zero_rpm})");
 
-        let fan_zero_rpm_path = if let Some(ref f) = self.amdgpu_device.fan_zero_rpm {
-            &f.path
-        } else {
-            return Ok(())
-        };
-        let mut file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(fan_zero_rpm_path)?;
+        let s = format!("{} ", if fan_zero_rpm { 1 } else { 0 });
 
-        let fan_zero_rpm = if fan_zero_rpm { 1 } else { 0 };
-        let fan_zero_rpm = format!("{fan_zero_rpm} ");
-        file.write_all(fan_zero_rpm.as_bytes())?;
-        Self::commit(&mut file)
+        od.push(Knob::FanZeroRpm, &f.path, vec![s], fan_zero_rpm, read);
+
+        Ok(())
     }
 
-    pub fn set_fan_target_rpm(&self, fan_target_rpm: u32) -> io::Result<()> {
+    pub fn stage_fan_target_rpm<'a>(&'a self, od: &mut OdCommitBuilder<'a>, fan_target_rpm: u32) -> io::Result<()> {
         let Some(ref acoustic_target_rpm_threshold) = self.amdgpu_device.acoustic_target_rpm_threshold
             else { return Ok(()) };
-        debug!("    Set acoustic_target_rpm_threshold ({fan_target_rpm})");
+        let read = || AcousticTargetRpmThreshold::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.rpm);
 
-        let mut file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&acoustic_target_rpm_threshold.path)?;
-        let fan_target_rpm = format!("{fan_target_rpm} ");
-        file.write_all(fan_target_rpm.as_bytes())?;
-        Self::commit(&mut file)
-    }
+        if read() == Some(fan_target_rpm) {
+            return Ok(());
+        }
 
-    pub fn set_default_fan_target_rpm(&self) -> io::Result<()> {
-        let Some(fan_target_rpm) = self.config_device.acoustic_target_rpm_threshold
-            else { return Ok(()) };
+        debug!("    Set acoustic_target_rpm_threshold ({fan_target_rpm})");
 
-        self.set_fan_target_rpm(fan_target_rpm)
+        od.push(
+            Knob::FanTargetRpm,
+            &acoustic_target_rpm_threshold.path,
+            vec![format!("{fan_target_rpm} ")],
+            fan_target_rpm,
+            read,
+        );
+
+        Ok(())
     }
 
     pub fn edited_fan_curve(fan_curve_points: &[(u8, u8)]) -> bool {
@@ -168,8 +880,8 @@ impl AppDevice {
     }
 
     pub fn validate_fan_curve(fan_curve: &FanCurve, fan_curve_points: &[(u8, u8)]) -> bool {
-        let temp_range = fan_curve.temp_range[0]..fan_curve.temp_range[1];
-        let fan_speed_range = fan_curve.fan_speed_range[0]..fan_curve.fan_speed_range[1];
+        let temp_range = fan_curve.temp_range[0]..=fan_curve.temp_range[1];
+        let fan_speed_range = fan_curve.fan_speed_range[0]..=fan_curve.fan_speed_range[1];
 
         for (i, (temp, fan_speed)) in fan_curve_points.iter().enumerate() {
             if !temp_range.contains(temp) || !fan_speed_range.contains(fan_speed) {
@@ -182,147 +894,828 @@ impl AppDevice {
         true
     }
 
-    pub fn set_fan_curve(&self) -> io::Result<()> {
+    pub fn stage_fan_curve<'a>(&'a self, od: &mut OdCommitBuilder<'a>, fan_curve_points: &[(u8, u8)]) -> io::Result<()> {
         let Some(ref fan_curve) = self.amdgpu_device.fan_curve else { return Ok(()) };
-        let Some(ref config_fan_curve) = self.config_device.fan_curve_points else { return Ok(()) };
-        debug!("    Set fan_curve points ({config_fan_curve:?})");
+        let read = || FanCurve::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.points);
 
-        if !Self::validate_fan_curve(fan_curve, config_fan_curve) {
-            return Ok(()); // through
+        if read().is_some_and(|points| points == fan_curve_points) {
+            return Ok(());
         }
 
-        let mut file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&fan_curve.path)?;
+        // The firmware curve at startup (the fallback without `fan_curve_points`) is written as is.
+        let fan_curve_points = if fan_curve_points == fan_curve.points {
+            fan_curve_points.to_vec()
+        } else {
+            fan_curve.resample(fan_curve_points)
+        };
 
-        for (i, (temp, fan_speed)) in config_fan_curve.iter().enumerate() {
-            let s = format!("{i} {temp} {fan_speed} ");
-            file.write_all(s.as_bytes())?;
+        if read().is_some_and(|points| points == fan_curve_points) {
+            return Ok(());
         }
 
-        Self::commit(&mut file)
+        debug!("    Set fan_curve points ({fan_curve_points:?})");
+
+        if !Self::validate_fan_curve(fan_curve, &fan_curve_points) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "fan_curve points are out of range (temp: {:?}, fan speed: {:?})",
+                    fan_curve.temp_range,
+                    fan_curve.fan_speed_range,
+                ),
+            ));
+        }
+
+        let cmds: Vec<String> = fan_curve_points
+            .iter()
+            .enumerate()
+            .map(|(i, (temp, fan_speed))| format!("{i} {temp} {fan_speed} "))
+            .collect();
+
+        od.push(Knob::FanCurve, &fan_curve.path, cmds, fan_curve_points, read);
+
+        Ok(())
     }
 
-    pub fn set_sclk_offset(&self) -> io::Result<()> {
+    pub fn stage_sclk_offset<'a>(&'a self, od: &mut OdCommitBuilder<'a>, sclk_offset: i32) -> io::Result<()> {
         if self.amdgpu_device.sclk_offset.is_none() {
             return Ok(());
         }
 
-        let Some(so) = self.config_device.sclk_offset else { return Ok(()) };
-        let mut file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&self.amdgpu_device.pp_od_clk_voltage_path)?;
-        let so = format!("s {so} ");
+        let read = || SclkOffset::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.current);
+
+        if read() == Some(sclk_offset) {
+            return Ok(());
+        }
 
-        debug!("    Set sclk_offset ({so}MHz)");
+        debug!("    Set sclk_offset ({sclk_offset}MHz)");
 
-        file.write_all(so.as_bytes())?;
-        Self::commit(&mut file)
+        od.push(
+            Knob::SclkOffset,
+            &self.amdgpu_device.pp_od_clk_voltage_path,
+            vec![format!("s {sclk_offset} ")],
+            sclk_offset,
+            read,
+        );
+
+        Ok(())
     }
 
-    pub fn set_vddgfx_offset(&self) -> io::Result<()> {
+    pub fn stage_vddgfx_offset<'a>(&'a self, od: &mut OdCommitBuilder<'a>, vddgfx_offset: i32) -> io::Result<()> {
         if self.amdgpu_device.vddgfx_offset.is_none() {
             return Ok(());
         }
 
-        let Some(vo) = self.config_device.vddgfx_offset else { return Ok(()) };
+        let read = || VddgfxOffset::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.current);
+
+        if read() == Some(vddgfx_offset) {
+            return Ok(());
+        }
+
+        debug!("    Set vddgfx_offset ({vddgfx_offset}mV)");
+
+        od.push(
+            Knob::VddgfxOffset,
+            &self.amdgpu_device.pp_od_clk_voltage_path,
+            vec![format!("vo {vddgfx_offset} ")],
+            vddgfx_offset,
+            read,
+        );
+
+        Ok(())
+    }
+
+    pub fn stage_sclk_limits<'a>(
+        &'a self,
+        od: &mut OdCommitBuilder<'a>,
+        min: Option<u32>,
+        max: Option<u32>,
+    ) -> io::Result<()> {
+        let read = || ClockLimits::sclk_from_sysfs_path(&self.amdgpu_device.sysfs_path);
+
+        self.stage_clock_limits(od, Knob::SclkLimits, "s", self.amdgpu_device.sclk_limits.as_ref(), read, [min, max])
+    }
+
+    pub fn stage_mclk_limits<'a>(
+        &'a self,
+        od: &mut OdCommitBuilder<'a>,
+        min: Option<u32>,
+        max: Option<u32>,
+    ) -> io::Result<()> {
+        let read = || ClockLimits::mclk_from_sysfs_path(&self.amdgpu_device.sysfs_path);
+
+        self.stage_clock_limits(od, Knob::MclkLimits, "m", self.amdgpu_device.mclk_limits.as_ref(), read, [min, max])
+    }
+
+    /// Stages `<s|m> <0|1> <MHz>` for `pp_od_clk_voltage`
+    fn stage_clock_limits<'a>(
+        &'a self,
+        od: &mut OdCommitBuilder<'a>,
+        knob: Knob,
+        cmd: &str,
+        limits: Option<&ClockLimits>,
+        read: impl Fn() -> Option<ClockLimits> + 'a,
+        min_max: [Option<u32>; 2],
+    ) -> io::Result<()> {
+        let Some(limits) = limits else { return Ok(()) };
+        let name = knob.name();
+        let current = read();
+        let current = current.as_ref().unwrap_or(limits);
+        let mut cmds: Vec<String> = Vec::with_capacity(2);
+        let mut targets: Vec<(usize, Option<u32>)> = Vec::with_capacity(2);
+
+        for (i, (clk, exposed, current)) in [
+            (min_max[0], limits.min, current.min),
+            (min_max[1], limits.max, current.max),
+        ].into_iter().enumerate() {
+            // e.g. Vega20 only exposes the max MCLK
+            let (Some(clk), Some(_)) = (clk, exposed) else { continue };
+
+            if current == Some(clk) {
+                continue;
+            }
+
+            if !limits.contains(clk) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{name} {clk}MHz is out of range ({:?})", limits.range),
+                ));
+            }
+
+            cmds.push(format!("{cmd} {i} {clk} "));
+            targets.push((i, Some(clk)));
+        }
+
+        if cmds.is_empty() {
+            return Ok(());
+        }
+
+        debug!("    Set {name} ({min_max:?}MHz)");
+
+        let indexes: Vec<usize> = targets.iter().map(|(i, _)| *i).collect();
+        let read = move || {
+            let current = read()?;
+            let min_max = [current.min, current.max];
+
+            Some(indexes.iter().map(|i| (*i, min_max[*i])).collect())
+        };
+
+        od.push(knob, &self.amdgpu_device.pp_od_clk_voltage_path, cmds, targets, read);
+
+        Ok(())
+    }
+
+    /// Stages `vc <point> <MHz> <mV>` for `pp_od_clk_voltage`
+    pub fn stage_vddc_curve<'a>(&'a self, od: &mut OdCommitBuilder<'a>, points: &[(u32, u32)]) -> io::Result<()> {
+        let Some(ref vddc_curve) = self.amdgpu_device.vddc_curve else { return Ok(()) };
+
+        if points.len() != vddc_curve.points.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("vddc_curve has {} points, but the device has {} points", points.len(), vddc_curve.points.len()),
+            ));
+        }
+
+        if let Some((i, p)) = points.iter().enumerate().find(|(i, p)| !vddc_curve.contains(*i, **p)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("vddc_curve point {i} {p:?} is out of range ({:?})", vddc_curve.ranges.get(i)),
+            ));
+        }
+
+        let read = || VddcCurve::from_sysfs_path(&self.amdgpu_device.sysfs_path).map(|current| current.points);
+
+        if read().is_some_and(|current| current == points) {
+            return Ok(());
+        }
+
+        debug!("    Set vddc_curve ({points:?})");
+
+        let cmds: Vec<String> = points
+            .iter()
+            .enumerate()
+            .map(|(i, (clk, volt))| format!("vc {i} {clk} {volt} "))
+            .collect();
+
+        od.push(Knob::VddcCurve, &self.amdgpu_device.pp_od_clk_voltage_path, cmds, points.to_vec(), read);
+
+        Ok(())
+    }
+
+    pub fn stage_sclk_states<'a>(&'a self, od: &mut OdCommitBuilder<'a>, states: &[(u32, u32)]) -> io::Result<()> {
+        let read = || ClockVoltageStates::sclk_from_sysfs_path(&self.amdgpu_device.sysfs_path);
+
+        self.stage_clock_voltage_states(od, Knob::SclkStates, "s", self.amdgpu_device.sclk_states.as_ref(), read, states)
+    }
+
+    pub fn stage_mclk_states<'a>(&'a self, od: &mut OdCommitBuilder<'a>, states: &[(u32, u32)]) -> io::Result<()> {
+        let read = || ClockVoltageStates::mclk_from_sysfs_path(&self.amdgpu_device.sysfs_path);
+
+        self.stage_clock_voltage_states(od, Knob::MclkStates, "m", self.amdgpu_device.mclk_states.as_ref(), read, states)
+    }
+
+    /// Stages `<s|m> <state> <MHz> <mV>` for `pp_od_clk_voltage`
+    fn stage_clock_voltage_states<'a>(
+        &'a self,
+        od: &mut OdCommitBuilder<'a>,
+        knob: Knob,
+        cmd: &str,
+        table: Option<&ClockVoltageStates>,
+        read: impl Fn() -> Option<ClockVoltageStates> + 'a,
+        states: &[(u32, u32)],
+    ) -> io::Result<()> {
+        let Some(table) = table else { return Ok(()) };
+        let name = knob.name();
+
+        if states.len() != table.states.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} has {} states, but the device has {} states", states.len(), table.states.len()),
+            ));
+        }
+
+        if let Some((i, state)) = states.iter().enumerate().find(|(_i, state)| !table.contains(**state)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{name} {i} {state:?} is out of range ({:?}, {:?})",
+                    table.clk_range,
+                    table.volt_range,
+                ),
+            ));
+        }
+
+        if read().is_some_and(|current| current.states == states) {
+            return Ok(());
+        }
+
+        debug!("    Set {name} ({states:?})");
+
+        let cmds: Vec<String> = states
+            .iter()
+            .enumerate()
+            .map(|(i, (clk, volt))| format!("{cmd} {i} {clk} {volt} "))
+            .collect();
+
+        od.push(
+            knob,
+            &self.amdgpu_device.pp_od_clk_voltage_path,
+            cmds,
+            states.to_vec(),
+            move || read().map(|current| current.states),
+        );
+
+        Ok(())
+    }
+
+    pub fn set_sclk_od(&self, percent: u32) -> io::Result<()> {
+        Self::set_od_percent("sclk_od", self.amdgpu_device.sclk_od.as_ref(), percent)
+    }
+
+    pub fn set_mclk_od(&self, percent: u32) -> io::Result<()> {
+        Self::set_od_percent("mclk_od", self.amdgpu_device.mclk_od.as_ref(), percent)
+    }
+
+    fn set_od_percent(name: &str, od: Option<&OdPercent>, percent: u32) -> io::Result<()> {
+        let Some(od) = od else { return Ok(()) };
+        let read = || fs::read_to_string(&od.path).ok().and_then(|s| s.trim().parse::<u32>().ok());
+
+        if read() == Some(percent) {
+            return Ok(());
+        }
+
+        debug!("    Set {name} ({percent}%)");
+        Self::write_verified(name, &percent, || fs::write(&od.path, percent.to_string()), read)
+    }
+
+    /// Writes the allowed levels to `pp_dpm_*`.
+    /// If the perf level is `manual` without a mask, all levels are allowed again.
+    /// The mask can't be read back, `pp_dpm_*` only shows the current level.
+    pub fn set_dpm_levels(&self, masks: &DpmLevelMasks, perf_level: DpmForcedLevel) -> io::Result<()> {
+        let mut first_err: Option<io::Error> = None;
+
+        for dpm_levels in &self.amdgpu_device.dpm_levels {
+            let mask = match masks.get(dpm_levels.clk_type) {
+                Some(mask) => mask.to_vec(),
+                None if perf_level == DpmForcedLevel::Manual => {
+                    dpm_levels.levels.iter().map(|(i, _)| *i).collect()
+                },
+                None => continue,
+            };
+
+            if let Err(e) = Self::set_dpm_level_mask(dpm_levels, &mask) {
+                first_err.get_or_insert(e);
+            }
+        }
+
+        match first_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn set_dpm_level_mask(dpm_levels: &DpmLevels, mask: &[u32]) -> io::Result<()> {
+        let name = dpm_levels.clk_type.name();
+
+        if mask.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} DPM levels are empty"),
+            ));
+        }
+
+        if let Some(i) = mask.iter().find(|i| !dpm_levels.contains(**i)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} DPM level {i} does not exist ({:?})", dpm_levels.levels),
+            ));
+        }
+
+        let mask: Vec<String> = mask.iter().map(|i| i.to_string()).collect();
+        let mask = mask.join(" ");
+
+        debug!("    Set {name} DPM levels ({mask})");
+
+        fs::write(&dpm_levels.path, mask)
+    }
+
+    /// Writes each command to `path` (without committing)
+    fn write_cmds(path: &Path, cmds: &[String]) -> io::Result<()> {
         let mut file = fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .open(&self.amdgpu_device.pp_od_clk_voltage_path)?;
-        let vo = format!("vo {vo} ");
+            .open(path)?;
 
-        debug!("    Set vddgfx_offset ({vo}mV)");
+        for c in cmds {
+            file.write_all(c.as_bytes())?;
+        }
 
-        file.write_all(vo.as_bytes())?;
-        Self::commit(&mut file)
+        Ok(())
     }
 
-    fn commit(file: &mut fs::File) -> io::Result<()> {
-        file.write_all(b"c")
+    /// Calls `write` and reads the value back with `read`,
+    /// retrying with backoff until it matches `expected`.
+    /// The firmware may reject or clamp a value without returning an error.
+    fn write_verified<T: PartialEq + fmt::Debug>(
+        name: &str,
+        expected: &T,
+        write: impl Fn() -> io::Result<()>,
+        read: impl Fn() -> Option<T>,
+    ) -> io::Result<()> {
+        retry_with_backoff(name, || {
+            write()?;
+
+            match Mismatch::check(name, expected, read()) {
+                Some(mismatch) => Err(io::Error::other(mismatch)),
+                None => Ok(()),
+            }
+        })
     }
 
     pub fn name_list(&self) -> Vec<String> {
         self.config_device.names()
     }
 
-    pub fn set_default_od_config(&self) -> Result<(), io::Error> {
+    pub fn default_config(&self) -> EffectiveConfig {
+        EffectiveConfig::from_defaults(&self.config_device, &self.snapshot)
+    }
+
+    /// Reads the current value of `knob` into `previous` if `config` writes it, to roll back to.
+    /// Returns `false` if the knob is written but the current value can't be restored,
+    /// e.g. DPM level masks, which can't be read back.
+    fn read_knob(&self, knob: Knob, config: &EffectiveConfig, previous: &mut EffectiveConfig) -> bool {
+        fn read<T>(written: bool, current: Option<T>, field: &mut T) -> bool {
+            if !written {
+                return true;
+            }
+
+            match current {
+                Some(v) => {
+                    *field = v;
+                    true
+                },
+                None => false,
+            }
+        }
+
+        let sysfs_path = &self.amdgpu_device.sysfs_path;
+        let written = knob.is_written(config);
+
+        match knob {
+            Knob::PerfLevel => read(
+                written,
+                DpmForcedLevel::get_from_sysfs(sysfs_path).ok(),
+                &mut previous.perf_level,
+            ),
+            // The previous heuristics can only be written back by selecting the CUSTOM profile.
+            Knob::CustomProfile => read(
+                written,
+                PowerProfile::get_current_profile_from_sysfs(sysfs_path)
+                    .filter(|profile| *profile == PowerProfile::CUSTOM)
+                    .and_then(|_| read_custom_profile(sysfs_path))
+                    .map(Some),
+                &mut previous.custom_profile,
+            ),
+            Knob::PowerProfile => read(
+                written,
+                PowerProfile::get_current_profile_from_sysfs(sysfs_path),
+                &mut previous.profile,
+            ),
+            Knob::PowerCap => read(
+                written,
+                self.read_power_cap_watt().map(Some),
+                &mut previous.power_cap_watt,
+            ),
+            Knob::FanZeroRpm => read(
+                written,
+                FanZeroRpm::from_sysfs_path(sysfs_path).map(|f| Some(f.flag)),
+                &mut previous.fan_zero_rpm,
+            ),
+            Knob::FanTargetTemp => read(
+                written,
+                FanTargetTemp::from_sysfs_path(sysfs_path).map(|fan| Some(fan.target_temp)),
+                &mut previous.fan_target_temperature,
+            ),
+            Knob::FanMinimumPwm => read(
+                written,
+                FanMinPwm::from_sysfs_path(sysfs_path).map(|fan| Some(fan.minimum_pwm)),
+                &mut previous.fan_minimum_pwm,
+            ),
+            Knob::FanTargetRpm => read(
+                written,
+                AcousticTargetRpmThreshold::from_sysfs_path(sysfs_path).map(|ac| Some(ac.rpm)),
+                &mut previous.acoustic_target_rpm_threshold,
+            ),
+            Knob::SclkOffset => read(
+                written,
+                SclkOffset::from_sysfs_path(sysfs_path).map(|sclk| Some(sclk.current)),
+                &mut previous.sclk_offset,
+            ),
+            Knob::VddgfxOffset => read(
+                written,
+                VddgfxOffset::from_sysfs_path(sysfs_path).map(|vddgfx| Some(vddgfx.current)),
+                &mut previous.vddgfx_offset,
+            ),
+            Knob::SclkLimits => {
+                let current = ClockLimits::sclk_from_sysfs_path(sysfs_path);
+                let mut min_max = [previous.min_sclk, previous.max_sclk];
+                let restorable = read(
+                    written,
+                    current.map(|sclk| [sclk.min, sclk.max]),
+                    &mut min_max,
+                );
+
+                [previous.min_sclk, previous.max_sclk] = min_max;
+
+                restorable
+            },
+            Knob::MclkLimits => {
+                let current = ClockLimits::mclk_from_sysfs_path(sysfs_path);
+                let mut min_max = [previous.min_mclk, previous.max_mclk];
+                let restorable = read(
+                    written,
+                    current.map(|mclk| [mclk.min, mclk.max]),
+                    &mut min_max,
+                );
+
+                [previous.min_mclk, previous.max_mclk] = min_max;
+
+                restorable
+            },
+            Knob::VddcCurve => read(
+                written,
+                VddcCurve::from_sysfs_path(sysfs_path).map(|curve| Some(curve.points)),
+                &mut previous.vddc_curve,
+            ),
+            Knob::SclkStates => read(
+                written,
+                ClockVoltageStates::sclk_from_sysfs_path(sysfs_path).map(|sclk| Some(sclk.states)),
+                &mut previous.sclk_states,
+            ),
+            Knob::MclkStates => read(
+                written,
+                ClockVoltageStates::mclk_from_sysfs_path(sysfs_path).map(|mclk| Some(mclk.states)),
+                &mut previous.mclk_states,
+            ),
+            Knob::FanCurve => read(
+                written,
+                FanCurve::from_sysfs_path(sysfs_path).map(|fan_curve| Some(fan_curve.points)),
+                &mut previous.fan_curve_points,
+            ),
+            Knob::SclkOd => read(
+                written,
+                OdPercent::from_sysfs_path(sysfs_path, "pp_sclk_od").map(|od| Some(od.current)),
+                &mut previous.sclk_od,
+            ),
+            Knob::MclkOd => read(
+                written,
+                OdPercent::from_sysfs_path(sysfs_path, "pp_mclk_od").map(|od| Some(od.current)),
+                &mut previous.mclk_od,
+            ),
+            // A mask in place with the `manual` perf level would be replaced with all levels.
+            // `PerfLevel` is read first, so `previous.perf_level` is the current one.
+            Knob::DpmLevels => {
+                previous.dpm_levels = DpmLevelMasks::default();
+
+                previous.perf_level != DpmForcedLevel::Manual
+                    || (config.dpm_levels.is_empty() && config.perf_level != DpmForcedLevel::Manual)
+            },
+        }
+    }
+
+    /// Writes `knob` of `config`, or stages it in `od` if it is an OD knob.
+    fn write_knob<'a>(&'a self, knob: Knob, config: &EffectiveConfig, od: &mut OdCommitBuilder<'a>) -> io::Result<()> {
+        fn opt<T>(v: Option<T>, f: impl FnOnce(T) -> io::Result<()>) -> io::Result<()> {
+            v.map_or(Ok(()), f)
+        }
+
+        match knob {
+            Knob::PerfLevel => self.set_perf_level(config.perf_level),
+            Knob::CustomProfile => self.set_effective_custom_profile(config),
+            Knob::PowerProfile => self.set_power_profile(config.profile),
+            Knob::PowerCap => opt(config.power_cap_watt, |v| self.set_power_cap(v)),
+            Knob::FanZeroRpm => opt(config.fan_zero_rpm, |v| self.stage_fan_zero_rpm(od, v)),
+            Knob::FanTargetTemp => opt(config.fan_target_temperature, |v| self.stage_fan_target_temp(od, v)),
+            Knob::FanMinimumPwm => opt(config.fan_minimum_pwm, |v| self.stage_fan_minimum_pwm(od, v)),
+            Knob::FanTargetRpm => opt(config.acoustic_target_rpm_threshold, |v| self.stage_fan_target_rpm(od, v)),
+            Knob::SclkOffset => opt(config.sclk_offset, |v| self.stage_sclk_offset(od, v)),
+            Knob::VddgfxOffset => opt(config.vddgfx_offset, |v| self.stage_vddgfx_offset(od, v)),
+            Knob::SclkLimits => self.stage_sclk_limits(od, config.min_sclk, config.max_sclk),
+            Knob::MclkLimits => self.stage_mclk_limits(od, config.min_mclk, config.max_mclk),
+            Knob::VddcCurve => opt(config.vddc_curve.as_deref(), |v| self.stage_vddc_curve(od, v)),
+            Knob::SclkStates => opt(config.sclk_states.as_deref(), |v| self.stage_sclk_states(od, v)),
+            Knob::MclkStates => opt(config.mclk_states.as_deref(), |v| self.stage_mclk_states(od, v)),
+            Knob::FanCurve => {
+                let fan_curve_points = config.fan_curve_points
+                    .as_deref()
+                    .filter(|points| Self::edited_fan_curve(points));
+
+                opt(fan_curve_points, |v| self.stage_fan_curve(od, v))
+            },
+            Knob::SclkOd => opt(config.sclk_od, |v| self.set_sclk_od(v)),
+            Knob::MclkOd => opt(config.mclk_od, |v| self.set_mclk_od(v)),
+            Knob::DpmLevels => self.set_dpm_levels(&config.dpm_levels, config.perf_level),
+        }
+    }
+
+    /// Writes a run of knobs of `config`:
+    /// a single non-OD knob, or OD knobs committed together once all of them are staged.
+    fn write_knobs(&self, knobs: &[Knob], config: &EffectiveConfig) -> Result<(), (Knob, io::Error)> {
+        let mut od = OdCommitBuilder::default();
+
+        for knob in knobs {
+            self.write_knob(*knob, config, &mut od).map_err(|e| (*knob, e))?;
+        }
+
+        od.commit_verified()
+    }
+
+    /// Writes all settings in `config` as a transaction, and records the status of each setting in `setting_status`.
+    /// If a write fails, the knobs written so far (including the failed one) are returned
+    /// to the values read before the transaction.
+    pub fn apply_effective_config(&mut self, config: &EffectiveConfig) -> Result<(), ApplyError> {
+        let mut previous = config.clone();
+        let not_restorable: Vec<Knob> = Knob::ALL
+            .into_iter()
+            .filter(|knob| !self.read_knob(*knob, config, &mut previous))
+            .collect();
+        let mut written = 0;
+
+        for knobs in Knob::ALL.chunk_by(|a, b| a.is_od() && b.is_od()) {
+            written += knobs.len();
+
+            let Err((knob, error)) = self.write_knobs(knobs, config) else { continue };
+
+            debug!(
+                "{} ({}):    Failed to set {} ({error:?}), roll back",
+                self.amdgpu_device.pci_bus,
+                self.amdgpu_device.device_name,
+                knob.name(),
+            );
+
+            let rollback = self.rollback(&previous, &Knob::ALL[..written], &not_restorable);
+
+            self.setting_status = Knob::ALL
+                .iter()
+                .enumerate()
+                .map(|(i, k)| {
+                    let status = if *k == knob {
+                        SettingStatus::from_error(&error)
+                    } else if let Some((_, e)) = rollback.failed.iter().find(|(name, _)| *name == k.name()) {
+                        SettingStatus::Failed(format!("rollback failed: {e}"))
+                    } else if rollback.not_restorable.contains(&k.name()) {
+                        SettingStatus::Failed("not restorable".to_string())
+                    } else if i >= written || !k.is_written(config) {
+                        SettingStatus::Skipped
+                    } else {
+                        SettingStatus::RolledBack
+                    };
+
+                    (k.name(), status)
+                })
+                .collect();
+            self.log_setting_status();
+
+            return Err(ApplyError { knob: knob.name(), error, rollback });
+        }
+
+        self.setting_status = Knob::ALL
+            .iter()
+            .map(|k| {
+                let status = if k.is_written(config) { SettingStatus::Applied } else { SettingStatus::Skipped };
+
+                (k.name(), status)
+            })
+            .collect();
+        self.log_setting_status();
+
+        Ok(())
+    }
+
+    fn log_setting_status(&self) {
         debug!(
-            "{} ({}): Set default settings",
+            "{} ({}): Status of the settings",
             self.amdgpu_device.pci_bus,
             self.amdgpu_device.device_name,
         );
 
-        for (result, s) in [
-            (self.set_default_perf_level(), "perf_level"),
-            (self.set_default_power_profile(), "power_profile"),
-            (self.set_default_power_cap(), "power_cap"),
-            (self.set_fan_zero_rpm(), "fan_zero_rpm"),
-            (self.set_default_fan_target_temp(), "fan_target_temp"),
-            (self.set_default_fan_minimum_pwm(), "fan_minimum_pwm"),
-            (self.set_default_fan_target_rpm(), "fan_target_rpm"),
-            (self.set_sclk_offset(), "sclk_offset"),
-            (self.set_vddgfx_offset(), "vddgfx_offset"),
-        ] {
-            if let Err(e) = result {
-                debug!(
-                    "{} ({}):    Failed to set {s} ({e:?})",
-                    self.amdgpu_device.pci_bus,
-                    self.amdgpu_device.device_name,
-                );
+        for (name, status) in &self.setting_status {
+            if *status != SettingStatus::Skipped {
+                debug!("    {name}: {status}");
             }
         }
+    }
 
-        if self.config_device.fan_curve_points.as_ref().is_some_and(|points| Self::edited_fan_curve(points)) {
-            let s = "fan_curve";
-            let r = self.set_fan_curve();
-            if let Err(e) = r {
-                debug!(
-                    "{} ({}):    Failed to set {s} ({e:?})",
-                    self.amdgpu_device.pci_bus,
-                    self.amdgpu_device.device_name,
-                );
-            }
+    /// Writes the previous values of `knobs` back in reverse order,
+    /// except the knobs in `not_restorable`.
+    fn rollback(&self, previous: &EffectiveConfig, knobs: &[Knob], not_restorable: &[Knob]) -> Rollback {
+        let (restorable, lost): (Vec<Knob>, Vec<Knob>) = knobs
+            .iter()
+            .partition(|knob| !not_restorable.contains(knob));
+        let chunks: Vec<_> = restorable.chunk_by(|a, b| a.is_od() && b.is_od()).collect();
+        let failed = chunks
+            .into_iter()
+            .rev()
+            .filter_map(|knobs| self.write_knobs(knobs, previous).err().map(|(knob, e)| (knob.name(), e)))
+            .collect();
+
+        // The DPM level masks are lost as soon as the perf level is written.
+        let dpm_levels = (!knobs.contains(&Knob::DpmLevels) && not_restorable.contains(&Knob::DpmLevels))
+            .then_some(Knob::DpmLevels);
+
+        Rollback {
+            failed,
+            not_restorable: lost.into_iter().chain(dpm_levels).map(|knob| knob.name()).collect(),
+        }
+    }
+
+    fn set_effective_custom_profile(&self, config: &EffectiveConfig) -> io::Result<()> {
+        match config.custom_profile {
+            Some(ref custom_profile) if config.profile == PowerProfile::CUSTOM => {
+                self.set_custom_profile(custom_profile)
+            },
+            _ => Ok(()),
         }
+    }
 
+    pub fn set_default_od_config(&mut self) -> Result<(), ApplyError> {
         debug!(
-            "{} ({}): Done setting default settings",
+            "{} ({}): Set default settings",
             self.amdgpu_device.pci_bus,
             self.amdgpu_device.device_name,
         );
 
-        Ok(())
-    }
+        let config = self.default_config();
+        let result = self.apply_effective_config(&config);
 
-    pub fn apply_config(&self, apply_config: &ParsedConfigEntry) -> Result<(), (io::Error, &str)> {
-        if let Some(perf_level) = apply_config.perf_level {
-            self.set_perf_level(perf_level)
-                .map_err(|e| (e, "perf_level"))?;
+        match result {
+            Ok(()) => debug!(
+                "{} ({}): Done setting default settings",
+                self.amdgpu_device.pci_bus,
+                self.amdgpu_device.device_name,
+            ),
+            Err(ref e) => debug!(
+                "{} ({}): Failed to set default settings ({e})",
+                self.amdgpu_device.pci_bus,
+                self.amdgpu_device.device_name,
+            ),
         }
-        if let Some(profile) = apply_config.profile {
-            self.set_power_profile(profile)
-                .map_err(|e| (e, "power_profile"))?;
+
+        result
+    }
+
+    /// Applies the defaults of the device with the overrides of `apply_config`,
+    /// so the result does not depend on the previously applied entry.
+    pub fn apply_config(&mut self, apply_config: &ParsedConfigEntry) -> Result<(), ApplyError> {
+        let config = self.default_config().with_entry(apply_config);
+
+        self.apply_effective_config(&config)
+    }
+
+    /// Returns the device to the state at startup:
+    /// resets `pp_od_clk_voltage` and `gpu_od/fan_ctrl/*` with `r`, then writes the snapshot back.
+    pub fn restore_snapshot(&mut self) {
+        debug!(
+            "{} ({}): Restore the state at startup",
+            self.amdgpu_device.pci_bus,
+            self.amdgpu_device.device_name,
+        );
+
+        if let Some(ref mut fan_control) = self.fan_control {
+            fan_control.release();
         }
-        if let Some(power_cap_watt) = apply_config.power_cap_watt {
-            self.set_power_cap(power_cap_watt)
-                .map_err(|e| (e, "power_cap"))?;
+
+        let od_path = self.amdgpu_device.has_od_clk_voltage().then_some(&self.amdgpu_device.pp_od_clk_voltage_path);
+        let paths: Vec<&PathBuf> = od_path.into_iter().chain(self.amdgpu_device.fan_ctrl_paths()).collect();
+
+        Self::reset_od_files(&paths);
+
+        let snapshot = self.snapshot.clone();
+        let _ = self.apply_effective_config(&snapshot);
+    }
+
+    /// Writes `r` to each file, then commits all of them with a single `c` (see `OdCommitBuilder`).
+    fn reset_od_files(paths: &[&PathBuf]) {
+        let reset: Vec<&PathBuf> = paths
+            .iter()
+            .copied()
+            .filter(|path| match Self::write_cmds(path, &["r".to_string()]) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("    Failed to reset {path:?} ({e:?})");
+                    false
+                },
+            })
+            .collect();
+        let Some(last) = reset.last() else { return };
+
+        if let Err(e) = Self::write_cmds(last, &["c".to_string()]) {
+            debug!("    Failed to commit {last:?} ({e:?})");
         }
-        if let Some(target_temp) = apply_config.fan_target_temperature {
-            self.set_fan_target_temp(target_temp)
-                .map_err(|e| (e, "fan_target_temp"))?;
+    }
+
+    /// Records the snapshot and the applied entries, so a new instance can recover after a crash.
+    pub fn save_journal(&self) {
+        let journal = Journal {
+            pid: std::process::id(),
+            snapshot: self.snapshot.clone(),
+            applied_entries: self.applied_entries.clone(),
+        };
+
+        if let Err(e) = journal.save(&self.amdgpu_device.pci_bus) {
+            debug!(
+                "{} ({}): Failed to save the journal ({e:?})",
+                self.amdgpu_device.pci_bus,
+                self.amdgpu_device.device_name,
+            );
         }
-        if let Some(minimum_pwm) = apply_config.fan_minimum_pwm {
-            self.set_fan_minimum_pwm(minimum_pwm)
-                .map_err(|e| (e, "fan_minimum_pwm"))?;
+    }
+
+    pub fn remove_journal(&self) {
+        Journal::remove(&self.amdgpu_device.pci_bus);
+    }
+
+    /// Re-applies the entries recorded in the journal if they still exist in the config.
+    /// The next detection releases them if the target process has exited meanwhile.
+    pub fn resume_journal_entries(&mut self) -> bool {
+        let names = std::mem::take(&mut self.journal_entries);
+        let entries: Vec<_> = names
+            .iter()
+            .filter_map(|name| self.config_device.entries.iter().find(|e| &e.name == name))
+            .collect();
+
+        if entries.is_empty() || entries.len() != names.len() {
+            return false;
         }
-        if let Some(fan_target_rpm) = apply_config.acoustic_target_rpm_threshold {
-            self.set_fan_target_rpm(fan_target_rpm)
-                .map_err(|e| (e, "fan_target_rpm"))?;
+
+        let Some(entry) = ParsedConfigEntry::merge_entries(entries.into_iter()) else { return false };
+
+        debug!(
+            "{} ({}): Resume the entries of the previous instance ({names:?})",
+            self.amdgpu_device.pci_bus,
+            self.amdgpu_device.device_name,
+        );
+
+        if let Err(e) = self.apply_config(&entry) {
+            debug!(
+                "{} ({}): Failed to apply {:?} ({e})",
+                self.amdgpu_device.pci_bus,
+                self.amdgpu_device.device_name,
+                entry.name,
+            );
+            return false;
         }
 
-        Ok(())
+        self.matched_entries = self.config_device.entries
+            .iter()
+            .filter(|e| names.contains(&e.name))
+            .map(|e| (e.clone(), Vec::new()))
+            .collect();
+        self.cache_entry = Some(entry);
+        self.applied_entries = names;
+        self.switch_state.switched_at = Some(Instant::now());
+
+        true
     }
 
     pub fn check_changed_default_config(&mut self, new_config_device: &ParsedConfigPerDevice) {
@@ -9,7 +9,7 @@ const HELP_MSG: &str = concat!(
     "    add\n",
     "        Add the config entry to the config file.\n",
     "        `--pci <String>` or `-i/--index <usize>` and --name <String>` must be specified.\n",
-    "        (`--perf_level, --profile` are optional)\n",
+    "        (`--match_mode, --cmdline_contains, --exe_path, --priority, --perf_level, --profile` are optional)\n",
     "FLAGS:\n",
     "    --procs\n",
     "        Dump all current process names.\n",
@@ -26,6 +26,8 @@ const HELP_MSG: &str = concat!(
     "ENV:\n",
     "    APS_CONFIG_PATH\n",
     "        Specify the config file path.\n",
+    "    APS_SYSFS_ROOT\n",
+    "        Read sysfs, hwmon and /proc under the specified directory (e.g. a tree captured from a real card).\n",
 );
 
 use crate::config::ConfigEntry;
@@ -33,7 +35,7 @@ use libdrm_amdgpu_sys::PCI;
 
 #[derive(Default)]
 pub enum SubCommand {
-    AddEntry((Option<PCI::BUS_INFO>, Option<usize>, ConfigEntry)),
+    AddEntry((Option<PCI::BUS_INFO>, Option<usize>, Box<ConfigEntry>)),
     #[default]
     Nop,
 }
@@ -75,6 +77,22 @@ impl MainOpt {
                     .next()
                     .map(|arg| arg.to_string())
                     .unwrap_or_else(|| panic!("`--name <String>` is missing.")),
+                "--match_mode" => entry.match_mode = args
+                    .next()
+                    .map(|arg| arg.to_string())
+                    .or_else(|| panic!("`--match_mode <String>` is missing.")),
+                "--cmdline_contains" => entry.cmdline_contains = args
+                    .next()
+                    .map(|arg| arg.to_string())
+                    .or_else(|| panic!("`--cmdline_contains <String>` is missing.")),
+                "--exe_path" => entry.exe_path = args
+                    .next()
+                    .map(|arg| arg.to_string())
+                    .or_else(|| panic!("`--exe_path <String>` is missing.")),
+                "--priority" => entry.priority = args
+                    .next()
+                    .map(|arg| arg.parse().unwrap_or_else(|_| panic!("`--priority <i32>` is invalid.")))
+                    .or_else(|| panic!("`--priority <i32>` is missing.")),
                 "--perf_level" => entry.perf_level = args
                     .next()
                     .map(|arg| arg.to_string())
@@ -101,7 +119,7 @@ impl MainOpt {
         // valid
         let _ = entry.parse().unwrap();
 
-        self.sub_command = SubCommand::AddEntry((pci, index, entry));
+        self.sub_command = SubCommand::AddEntry((pci, index, Box::new(entry)));
     }
 
     pub fn parse() -> Self {
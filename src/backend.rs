@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use libdrm_amdgpu_sys::{AMDGPU, PCI};
+use proc_prog_name::ProcProgEntry;
+
+static BACKEND: OnceLock<Backend> = OnceLock::new();
+
+/// Where devices and processes are read from.
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// The running system
+    Host,
+    /// A directory tree captured from a real system (`<root>/sys`, `<root>/proc`, ...),
+    /// specified with `APS_SYSFS_ROOT`
+    Root(PathBuf),
+}
+
+impl Backend {
+    pub const ENV_ROOT: &str = "APS_SYSFS_ROOT";
+
+    pub fn get() -> &'static Self {
+        BACKEND.get_or_init(|| match std::env::var_os(Self::ENV_ROOT) {
+            Some(root) if !root.is_empty() => Self::Root(PathBuf::from(root)),
+            _ => Self::Host,
+        })
+    }
+
+    /// `path` (absolute) under the root
+    pub fn path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+
+        match self {
+            Self::Host => path.to_path_buf(),
+            Self::Root(root) => root.join(path.strip_prefix("/").unwrap_or(path)),
+        }
+    }
+
+    pub fn get_all_amdgpu_pci_bus(&self) -> Vec<PCI::BUS_INFO> {
+        if let Self::Host = self {
+            return AMDGPU::get_all_amdgpu_pci_bus();
+        }
+
+        // devices bound to amdgpu are listed in the driver directory with the bus id
+        let Ok(dir) = fs::read_dir(self.path("/sys/bus/pci/drivers/amdgpu")) else { return Vec::new() };
+        let mut pci_devs: Vec<PCI::BUS_INFO> = dir
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect();
+
+        pci_devs.sort_by_key(|pci| pci.to_string());
+
+        pci_devs
+    }
+
+    pub fn get_sysfs_path(&self, pci_bus: &PCI::BUS_INFO) -> PathBuf {
+        match self {
+            Self::Host => pci_bus.get_sysfs_path(),
+            Self::Root(_) => self.path(format!("/sys/bus/pci/devices/{pci_bus}")),
+        }
+    }
+
+    pub fn get_hwmon_path(&self, pci_bus: &PCI::BUS_INFO) -> Option<PathBuf> {
+        if let Self::Host = self {
+            return pci_bus.get_hwmon_path();
+        }
+
+        let mut hwmon: Vec<PathBuf> = fs::read_dir(self.get_sysfs_path(pci_bus).join("hwmon"))
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with("hwmon")))
+            .map(|entry| entry.path())
+            .collect();
+
+        hwmon.sort();
+        hwmon.into_iter().next()
+    }
+
+    pub fn get_all_proc_prog_entries(&self) -> Vec<ProcProgEntry> {
+        if let Self::Host = self {
+            return ProcProgEntry::get_all_proc_prog_entries();
+        }
+
+        let Ok(dir) = fs::read_dir(self.path("/proc")) else { return Vec::new() };
+
+        dir.filter_map(|entry| {
+            let entry = entry.ok()?;
+            let pid: i32 = entry.file_name().to_str()?.parse().ok()?;
+            let name = fs::read_to_string(entry.path().join("comm")).ok()?.trim_end().to_string();
+
+            Some(ProcProgEntry { pid, name })
+        }).collect()
+    }
+
+    pub fn update_entries_with_name_filter(&self, procs: &mut Vec<ProcProgEntry>, names: &[String]) {
+        if let Self::Host = self {
+            return ProcProgEntry::update_entries_with_name_filter(procs, names);
+        }
+
+        *procs = self.get_all_proc_prog_entries();
+        procs.retain(|p| names.contains(&p.name));
+    }
+}
@@ -1,6 +1,15 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
 use libdrm_amdgpu_sys::{AMDGPU, PCI};
 use AMDGPU::{PowerProfile, DpmForcedLevel};
 use serde::{Deserialize, Serialize};
+use proc_prog_name::ProcProgEntry;
+
+use crate::amdgpu_device::{interpolate_fan_speed, DpmClockType};
+use crate::matcher::NameMatcher;
+use crate::process;
+use crate::utils::PROFILE_LIST;
 
 #[derive(Debug, Clone)]
 pub struct ParsedConfig {
@@ -14,13 +23,27 @@ pub struct ParsedConfigPerDevice {
     pub default_power_cap_watt: Option<u32>,
     pub default_perf_level: DpmForcedLevel,
     pub default_profile: PowerProfile,
+    pub custom_profile: Option<Vec<Vec<i64>>>,
     pub default_fan_target_temperature: Option<u32>,
     pub default_fan_minimum_pwm: Option<u32>,
     pub sclk_offset: Option<i32>,
     pub vddgfx_offset: Option<i32>,
+    pub min_sclk: Option<u32>,
+    pub max_sclk: Option<u32>,
+    pub min_mclk: Option<u32>,
+    pub max_mclk: Option<u32>,
+    pub vddc_curve: Option<Vec<(u32, u32)>>,
+    pub sclk_states: Option<Vec<(u32, u32)>>,
+    pub mclk_states: Option<Vec<(u32, u32)>>,
+    pub sclk_od: Option<u32>,
+    pub mclk_od: Option<u32>,
+    pub dpm_levels: DpmLevelMasks,
     pub fan_zero_rpm: Option<bool>,
     pub acoustic_target_rpm_threshold: Option<u32>,
     pub fan_curve_points: Option<Vec<(u8, u8)>>,
+    pub software_fan_curve: Option<SoftwareFanCurve>,
+    pub min_dwell_secs: u64,
+    pub restore_on_exit: bool,
     pub entries: Vec<ParsedConfigEntry>,
 }
 
@@ -29,29 +52,168 @@ impl ParsedConfigPerDevice {
         self.entries.iter().map(|e| e.name.clone()).collect()
     }
 
+    pub fn has_name_pattern(&self) -> bool {
+        self.entries.iter().any(|e| !e.name_matcher.is_exact())
+    }
+
+    pub fn has_descendants_entry(&self) -> bool {
+        self.entries.iter().any(|e| e.include_descendants)
+    }
+
     pub fn is_default_changed(&self, new: &Self) -> bool {
         self.default_power_cap_watt != new.default_power_cap_watt
         || self.default_perf_level != new.default_perf_level
         || self.default_profile != new.default_profile
+        || self.custom_profile != new.custom_profile
         || self.default_fan_target_temperature != new.default_fan_target_temperature
         || self.default_fan_minimum_pwm != new.default_fan_minimum_pwm
         || self.sclk_offset != new.sclk_offset
         || self.vddgfx_offset != new.vddgfx_offset
+        || self.min_sclk != new.min_sclk
+        || self.max_sclk != new.max_sclk
+        || self.min_mclk != new.min_mclk
+        || self.max_mclk != new.max_mclk
+        || self.vddc_curve != new.vddc_curve
+        || self.sclk_states != new.sclk_states
+        || self.mclk_states != new.mclk_states
+        || self.sclk_od != new.sclk_od
+        || self.mclk_od != new.mclk_od
+        || self.dpm_levels != new.dpm_levels
         || self.fan_zero_rpm != new.fan_zero_rpm
         || self.acoustic_target_rpm_threshold != new.acoustic_target_rpm_threshold
         || self.fan_curve_points != new.fan_curve_points
+        || self.software_fan_curve != new.software_fan_curve
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ParsedConfigEntry {
     pub name: String,
+    pub name_matcher: NameMatcher,
+    pub cmdline_contains: Option<String>,
+    pub exe_path: Option<PathBuf>,
+    pub include_descendants: bool,
+    pub cgroup: Option<NameMatcher>,
+    pub unit: Option<NameMatcher>,
+    pub flatpak_id: Option<String>,
+    pub env: BTreeMap<String, String>,
+    pub uids: Vec<u32>,
+    pub require_device_usage: bool,
+    pub min_gfx_busy_percent: Option<u32>,
+    pub priority: i32,
+    pub merge: bool,
+    pub apply_delay_secs: Option<u64>,
+    pub restore_grace_secs: Option<u64>,
     pub perf_level: Option<DpmForcedLevel>,
     pub profile: Option<PowerProfile>,
+    pub custom_profile: Option<Vec<Vec<i64>>>,
     pub power_cap_watt: Option<u32>,
     pub fan_target_temperature: Option<u32>,
     pub fan_minimum_pwm: Option<u32>,
     pub acoustic_target_rpm_threshold: Option<u32>,
+    pub sclk_offset: Option<i32>,
+    pub vddgfx_offset: Option<i32>,
+    pub min_sclk: Option<u32>,
+    pub max_sclk: Option<u32>,
+    pub min_mclk: Option<u32>,
+    pub max_mclk: Option<u32>,
+    pub vddc_curve: Option<Vec<(u32, u32)>>,
+    pub sclk_states: Option<Vec<(u32, u32)>>,
+    pub mclk_states: Option<Vec<(u32, u32)>>,
+    pub sclk_od: Option<u32>,
+    pub mclk_od: Option<u32>,
+    pub dpm_levels: DpmLevelMasks,
+    pub fan_zero_rpm: Option<bool>,
+    pub fan_curve_points: Option<Vec<(u8, u8)>>,
+    pub software_fan_curve: Option<SoftwareFanCurve>,
+}
+
+impl ParsedConfigEntry {
+    /// Combines the matched entries field by field.
+    /// Fields set in earlier (higher priority) entries take precedence.
+    pub fn merge_entries<'a, I: Iterator<Item = &'a Self>>(mut entries: I) -> Option<Self> {
+        let mut merged = entries.next()?.clone();
+
+        for e in entries {
+            merged.name = format!("{} + {}", merged.name, e.name);
+            merged.apply_delay_secs = merged.apply_delay_secs.or(e.apply_delay_secs);
+            merged.restore_grace_secs = merged.restore_grace_secs.or(e.restore_grace_secs);
+            merged.perf_level = merged.perf_level.or(e.perf_level);
+            merged.profile = merged.profile.or(e.profile);
+            merged.custom_profile = merged.custom_profile.or_else(|| e.custom_profile.clone());
+            merged.power_cap_watt = merged.power_cap_watt.or(e.power_cap_watt);
+            merged.fan_target_temperature = merged.fan_target_temperature.or(e.fan_target_temperature);
+            merged.fan_minimum_pwm = merged.fan_minimum_pwm.or(e.fan_minimum_pwm);
+            merged.acoustic_target_rpm_threshold = merged.acoustic_target_rpm_threshold
+                .or(e.acoustic_target_rpm_threshold);
+            merged.sclk_offset = merged.sclk_offset.or(e.sclk_offset);
+            merged.vddgfx_offset = merged.vddgfx_offset.or(e.vddgfx_offset);
+            merged.min_sclk = merged.min_sclk.or(e.min_sclk);
+            merged.max_sclk = merged.max_sclk.or(e.max_sclk);
+            merged.min_mclk = merged.min_mclk.or(e.min_mclk);
+            merged.max_mclk = merged.max_mclk.or(e.max_mclk);
+            merged.vddc_curve = merged.vddc_curve.or_else(|| e.vddc_curve.clone());
+            merged.sclk_states = merged.sclk_states.or_else(|| e.sclk_states.clone());
+            merged.mclk_states = merged.mclk_states.or_else(|| e.mclk_states.clone());
+            merged.sclk_od = merged.sclk_od.or(e.sclk_od);
+            merged.mclk_od = merged.mclk_od.or(e.mclk_od);
+            merged.dpm_levels = merged.dpm_levels.or(&e.dpm_levels);
+            merged.fan_zero_rpm = merged.fan_zero_rpm.or(e.fan_zero_rpm);
+            merged.fan_curve_points = merged.fan_curve_points.or_else(|| e.fan_curve_points.clone());
+            merged.software_fan_curve = merged.software_fan_curve.or_else(|| e.software_fan_curve.clone());
+        }
+
+        Some(merged)
+    }
+
+    pub fn is_match(&self, proc: &ProcProgEntry) -> bool {
+        if !self.name_matcher.is_match(&proc.name) {
+            return false;
+        }
+
+        if !self.uids.is_empty() && !process::get_uid(proc.pid).is_some_and(|uid| self.uids.contains(&uid)) {
+            return false;
+        }
+
+        let cmdline_matched = self.cmdline_contains.as_ref().is_none_or(|s| {
+            process::get_cmdline(proc.pid).is_some_and(|cmdline| cmdline.contains(s.as_str()))
+        });
+        let exe_path_matched = self.exe_path.as_ref().is_none_or(|path| {
+            process::get_exe_path(proc.pid).is_some_and(|exe| &exe == path)
+        });
+
+        cmdline_matched && exe_path_matched && self.is_match_cgroup(proc.pid) && self.is_match_env(proc.pid)
+    }
+
+    fn is_match_env(&self, pid: i32) -> bool {
+        if self.env.is_empty() {
+            return true;
+        }
+
+        process::get_environ_values(pid, self.env.keys())
+            .iter()
+            .zip(self.env.values())
+            .all(|(current, expected)| current.as_ref() == Some(expected))
+    }
+
+    fn is_match_cgroup(&self, pid: i32) -> bool {
+        if self.cgroup.is_none() && self.unit.is_none() && self.flatpak_id.is_none() {
+            return true;
+        }
+
+        let paths = process::get_cgroup_paths(pid);
+        let cgroup_matched = self.cgroup.as_ref().is_none_or(|m| {
+            paths.iter().any(|path| m.is_match(path))
+        });
+        let unit_matched = self.unit.as_ref().is_none_or(|m| {
+            process::units_from_cgroup_paths(&paths).any(|unit| m.is_match(unit))
+        });
+        let flatpak_id_matched = self.flatpak_id.as_ref().is_none_or(|id| {
+            process::flatpak_id_from_cgroup_paths(&paths).is_some_and(|flatpak_id| &flatpak_id == id)
+        });
+
+        cgroup_matched && unit_matched && flatpak_id_matched
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -67,6 +229,7 @@ pub struct ConfigPerDevice {
     pub _power_cap_watt_range: Option<[u32; 2]>,
     pub default_perf_level: Option<String>,
     pub default_profile: Option<String>,
+    pub custom_profile: Option<Vec<Vec<i64>>>,
     pub default_fan_target_temperature: Option<u32>,
     pub _fan_target_temperature_range: Option<[u32; 2]>,
     pub default_fan_minimum_pwm: Option<u32>,
@@ -75,24 +238,162 @@ pub struct ConfigPerDevice {
     pub _sclk_offset_range: Option<[i32; 2]>,
     pub vddgfx_offset: Option<i32>,
     pub _vddgfx_offset_range: Option<[i32; 2]>,
+    pub min_sclk: Option<u32>,
+    pub max_sclk: Option<u32>,
+    pub _sclk_range: Option<[u32; 2]>,
+    pub min_mclk: Option<u32>,
+    pub max_mclk: Option<u32>,
+    pub _mclk_range: Option<[u32; 2]>,
+    pub vddc_curve: Option<Vec<(u32, u32)>>,
+    pub _vddc_curve_range: Option<Vec<([u32; 2], [u32; 2])>>,
+    pub sclk_states: Option<Vec<(u32, u32)>>,
+    pub _sclk_states_range: Option<[u32; 2]>,
+    pub mclk_states: Option<Vec<(u32, u32)>>,
+    pub _mclk_states_range: Option<[u32; 2]>,
+    pub _vddc_range: Option<[u32; 2]>,
+    pub sclk_od: Option<u32>,
+    pub mclk_od: Option<u32>,
+    pub dpm_levels: Option<DpmLevelMasks>,
+    pub _dpm_levels: Option<BTreeMap<String, Vec<String>>>,
     pub fan_zero_rpm: Option<bool>,
     pub acoustic_target_rpm_threshold: Option<u32>,
     pub _acoustic_target_rpm_threshold_range: Option<[u32; 2]>,
     pub fan_curve_points: Option<Vec<(u8, u8)>>,
     pub _fan_curve_temp_range: Option<[u8; 2]>,
     pub _fan_curve_fan_speed_range: Option<[u8; 2]>,
+    pub software_fan_curve: Option<SoftwareFanCurve>,
+    pub min_dwell_secs: Option<u64>,
+    pub restore_on_exit: Option<bool>,
     pub entries: Vec<ConfigEntry>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct ConfigEntry {
     pub name: String,
+    pub match_mode: Option<String>,
+    pub cmdline_contains: Option<String>,
+    pub exe_path: Option<String>,
+    pub include_descendants: Option<bool>,
+    pub cgroup: Option<String>,
+    pub unit: Option<String>,
+    pub flatpak_id: Option<String>,
+    pub env: Option<BTreeMap<String, String>>,
+    pub uid: Option<Vec<u32>>,
+    pub user: Option<Vec<String>>,
+    pub require_device_usage: Option<bool>,
+    pub min_gfx_busy_percent: Option<u32>,
+    pub priority: Option<i32>,
+    pub merge: Option<bool>,
+    pub apply_delay_secs: Option<u64>,
+    pub restore_grace_secs: Option<u64>,
     pub perf_level: Option<String>,
     pub profile: Option<String>,
+    pub custom_profile: Option<Vec<Vec<i64>>>,
     pub power_cap_watt: Option<u32>,
     pub fan_target_temperature: Option<u32>,
     pub fan_minimum_pwm: Option<u32>,
     pub acoustic_target_rpm_threshold: Option<u32>,
+    pub sclk_offset: Option<i32>,
+    pub vddgfx_offset: Option<i32>,
+    pub min_sclk: Option<u32>,
+    pub max_sclk: Option<u32>,
+    pub min_mclk: Option<u32>,
+    pub max_mclk: Option<u32>,
+    pub vddc_curve: Option<Vec<(u32, u32)>>,
+    pub sclk_states: Option<Vec<(u32, u32)>>,
+    pub mclk_states: Option<Vec<(u32, u32)>>,
+    pub sclk_od: Option<u32>,
+    pub mclk_od: Option<u32>,
+    pub dpm_levels: Option<DpmLevelMasks>,
+    pub fan_zero_rpm: Option<bool>,
+    pub fan_curve_points: Option<Vec<(u8, u8)>>,
+    pub software_fan_curve: Option<SoftwareFanCurve>,
+}
+
+/// Allowed DPM level indexes per clock domain (`pp_dpm_*`).
+/// Requires the `manual` perf level, which is selected automatically.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DpmLevelMasks {
+    pub sclk: Option<Vec<u32>>,
+    pub mclk: Option<Vec<u32>>,
+    pub fclk: Option<Vec<u32>>,
+    pub socclk: Option<Vec<u32>>,
+    pub pcie: Option<Vec<u32>>,
+}
+
+impl DpmLevelMasks {
+    pub fn get(&self, clk_type: DpmClockType) -> Option<&[u32]> {
+        let mask = match clk_type {
+            DpmClockType::Sclk => &self.sclk,
+            DpmClockType::Mclk => &self.mclk,
+            DpmClockType::Fclk => &self.fclk,
+            DpmClockType::Socclk => &self.socclk,
+            DpmClockType::Pcie => &self.pcie,
+        };
+
+        mask.as_deref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        DpmClockType::ALL.iter().all(|clk_type| self.get(*clk_type).is_none())
+    }
+
+    /// Fills the unset clock domains with `other`.
+    pub fn or(self, other: &Self) -> Self {
+        Self {
+            sclk: self.sclk.or_else(|| other.sclk.clone()),
+            mclk: self.mclk.or_else(|| other.mclk.clone()),
+            fclk: self.fclk.or_else(|| other.fclk.clone()),
+            socclk: self.socclk.or_else(|| other.socclk.clone()),
+            pcie: self.pcie.or_else(|| other.pcie.clone()),
+        }
+    }
+}
+
+/// Fan curve driven by the daemon through hwmon `pwm1`,
+/// for GPUs without `gpu_od/fan_ctrl/fan_curve`.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SoftwareFanCurve {
+    pub sensor: Option<String>, // "edge" (default), "junction", "mem"
+    pub points: Vec<(u8, u8)>, // (temp [C], fan speed [%])
+    pub hysteresis: Option<u8>, // [C]
+    pub max_step: Option<u8>, // [%] per second
+}
+
+impl SoftwareFanCurve {
+    pub const SENSOR_LIST: &[&str] = &["edge", "junction", "mem"];
+    pub const DEFAULT_HYSTERESIS: u8 = 3;
+    pub const DEFAULT_MAX_STEP: u8 = 5;
+
+    pub fn sensor(&self) -> &str {
+        self.sensor.as_deref().unwrap_or("edge")
+    }
+
+    pub fn fan_speed(&self, temp: u32) -> u8 {
+        interpolate_fan_speed(&self.points, temp)
+    }
+
+    fn validate(&self) -> Result<(), ParseConfigError> {
+        let err = |reason: &str| Err(ParseConfigError::InvalidSoftwareFanCurve(reason.to_string()));
+
+        if !Self::SENSOR_LIST.contains(&self.sensor()) {
+            return err(&format!("`sensor` must be one of the following: {:?}", Self::SENSOR_LIST));
+        }
+
+        if self.points.is_empty() {
+            return err("`points` is empty");
+        }
+
+        if self.points.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return err("temperatures in `points` must be in ascending order");
+        }
+
+        if self.points.iter().any(|(_, fan_speed)| *fan_speed > 100) {
+            return err("fan speed in `points` must be 0-100");
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -101,8 +402,13 @@ pub enum ParseConfigError {
     InvalidPci(String),
     PciIsEmpty,
     EntryNameIsEmpty,
+    RelativeExePath(String),
+    UnknownUser(String),
     InvalidPerfLevel(String),
     InvalidProfile(String),
+    InvalidMatchMode(String),
+    InvalidPattern(String, String),
+    InvalidSoftwareFanCurve(String),
     FromStrError,
 }
 
@@ -165,7 +471,12 @@ impl ConfigPerDevice {
 
         let default_perf_level = self.parse_default_perf_level()?;
         let default_profile = self.parse_default_power_profile()?;
+        let software_fan_curve = parse_software_fan_curve(&self.software_fan_curve)?;
         let entries: Result<Vec<ParsedConfigEntry>, ParseConfigError> = self.entries.iter().map(|entry| entry.parse()).collect();
+        let mut entries = entries?;
+
+        // Higher priority first, keeping the order in the config file for the same priority.
+        entries.sort_by_key(|e| std::cmp::Reverse(e.priority));
 
         Ok(ParsedConfigPerDevice {
             pci,
@@ -173,14 +484,28 @@ impl ConfigPerDevice {
             default_power_cap_watt: self.default_power_cap_watt,
             default_perf_level,
             default_profile,
+            custom_profile: self.custom_profile.clone(),
             default_fan_target_temperature: self.default_fan_target_temperature,
             default_fan_minimum_pwm: self.default_fan_minimum_pwm,
             sclk_offset: self.sclk_offset,
             vddgfx_offset: self.vddgfx_offset,
+            min_sclk: self.min_sclk,
+            max_sclk: self.max_sclk,
+            min_mclk: self.min_mclk,
+            max_mclk: self.max_mclk,
+            vddc_curve: self.vddc_curve.clone(),
+            sclk_states: self.sclk_states.clone(),
+            mclk_states: self.mclk_states.clone(),
+            sclk_od: self.sclk_od,
+            mclk_od: self.mclk_od,
+            dpm_levels: self.dpm_levels.clone().unwrap_or_default(),
             fan_zero_rpm: self.fan_zero_rpm,
             acoustic_target_rpm_threshold: self.acoustic_target_rpm_threshold,
             fan_curve_points: self.fan_curve_points.clone(),
-            entries: entries?,
+            software_fan_curve,
+            min_dwell_secs: self.min_dwell_secs.unwrap_or(0),
+            restore_on_exit: self.restore_on_exit.unwrap_or(false),
+            entries,
         })
     }
 }
@@ -214,24 +539,148 @@ impl ConfigEntry {
         Ok(profile)
     }
 
+    fn parse_name_matcher(&self) -> Result<NameMatcher, ParseConfigError> {
+        let matcher = match self.match_mode.as_deref() {
+            None | Some("exact") => return Ok(NameMatcher::exact(&self.name)),
+            Some("glob") => NameMatcher::glob(&self.name),
+            Some("regex") => NameMatcher::regex(&self.name),
+            Some(s) => return Err(ParseConfigError::InvalidMatchMode(s.to_string())),
+        };
+
+        matcher.map_err(|e| ParseConfigError::InvalidPattern(self.name.clone(), e.to_string()))
+    }
+
+    fn parse_glob(pattern: &Option<String>) -> Result<Option<NameMatcher>, ParseConfigError> {
+        let Some(pattern) = pattern else { return Ok(None) };
+
+        NameMatcher::glob(pattern)
+            .map(Some)
+            .map_err(|e| ParseConfigError::InvalidPattern(pattern.clone(), e.to_string()))
+    }
+
+    fn parse_uids(&self) -> Result<Vec<u32>, ParseConfigError> {
+        let mut uids = self.uid.clone().unwrap_or_default();
+
+        for user_name in self.user.iter().flatten() {
+            let Some(uid) = process::find_uid_by_user_name(user_name) else {
+                return Err(ParseConfigError::UnknownUser(user_name.to_string()));
+            };
+
+            uids.push(uid);
+        }
+
+        Ok(uids)
+    }
+
+    fn parse_exe_path(&self) -> Result<Option<PathBuf>, ParseConfigError> {
+        let Some(ref s) = self.exe_path else { return Ok(None) };
+        let path = PathBuf::from(s);
+
+        if !path.is_absolute() {
+            return Err(ParseConfigError::RelativeExePath(s.to_string()));
+        }
+
+        Ok(Some(path))
+    }
+
     pub fn parse(&self) -> Result<ParsedConfigEntry, ParseConfigError> {
         if self.name.is_empty() {
             return Err(ParseConfigError::EntryNameIsEmpty);
         }
 
         let name = self.name.clone();
+        let name_matcher = self.parse_name_matcher()?;
+        let cmdline_contains = self.cmdline_contains.clone();
+        let exe_path = self.parse_exe_path()?;
+        let include_descendants = self.include_descendants.unwrap_or(false);
+        let cgroup = Self::parse_glob(&self.cgroup)?;
+        let unit = Self::parse_glob(&self.unit)?;
+        let flatpak_id = self.flatpak_id.clone();
+        let env = self.env.clone().unwrap_or_default();
+        let uids = self.parse_uids()?;
+        let require_device_usage = self.require_device_usage.unwrap_or(false);
+        let min_gfx_busy_percent = self.min_gfx_busy_percent;
+        let priority = self.priority.unwrap_or(0);
+        let merge = self.merge.unwrap_or(false);
+        let apply_delay_secs = self.apply_delay_secs;
+        let restore_grace_secs = self.restore_grace_secs;
         let perf_level = self.parse_perf_level()?;
         let profile = self.parse_power_profile()?;
+        let custom_profile = self.custom_profile.clone();
         let power_cap_watt = self.power_cap_watt;
         let fan_target_temperature = self.fan_target_temperature;
         let fan_minimum_pwm = self.fan_minimum_pwm;
         let acoustic_target_rpm_threshold = self.acoustic_target_rpm_threshold;
-
-        Ok(ParsedConfigEntry { name, perf_level, profile, power_cap_watt, fan_target_temperature, fan_minimum_pwm, acoustic_target_rpm_threshold })
+        let sclk_offset = self.sclk_offset;
+        let vddgfx_offset = self.vddgfx_offset;
+        let min_sclk = self.min_sclk;
+        let max_sclk = self.max_sclk;
+        let min_mclk = self.min_mclk;
+        let max_mclk = self.max_mclk;
+        let vddc_curve = self.vddc_curve.clone();
+        let sclk_states = self.sclk_states.clone();
+        let mclk_states = self.mclk_states.clone();
+        let sclk_od = self.sclk_od;
+        let mclk_od = self.mclk_od;
+        let dpm_levels = self.dpm_levels.clone().unwrap_or_default();
+        let fan_zero_rpm = self.fan_zero_rpm;
+        let fan_curve_points = self.fan_curve_points.clone();
+        let software_fan_curve = parse_software_fan_curve(&self.software_fan_curve)?;
+
+        Ok(ParsedConfigEntry {
+            name,
+            name_matcher,
+            cmdline_contains,
+            exe_path,
+            include_descendants,
+            cgroup,
+            unit,
+            flatpak_id,
+            env,
+            uids,
+            require_device_usage,
+            min_gfx_busy_percent,
+            priority,
+            merge,
+            apply_delay_secs,
+            restore_grace_secs,
+            perf_level,
+            profile,
+            custom_profile,
+            power_cap_watt,
+            fan_target_temperature,
+            fan_minimum_pwm,
+            acoustic_target_rpm_threshold,
+            sclk_offset,
+            vddgfx_offset,
+            min_sclk,
+            max_sclk,
+            min_mclk,
+            max_mclk,
+            vddc_curve,
+            sclk_states,
+            mclk_states,
+            sclk_od,
+            mclk_od,
+            dpm_levels,
+            fan_zero_rpm,
+            fan_curve_points,
+            software_fan_curve,
+        })
     }
 }
 
-fn perf_level_from_str(s: &str) -> Option<DpmForcedLevel> {
+fn parse_software_fan_curve(
+    curve: &Option<SoftwareFanCurve>,
+) -> Result<Option<SoftwareFanCurve>, ParseConfigError> {
+    let Some(curve) = curve else { return Ok(None) };
+
+    curve.validate()?;
+
+    Ok(Some(curve.clone()))
+}
+
+pub fn perf_level_from_str(s: &str) -> Option<DpmForcedLevel> {
     let perf_level = match s {
         "auto" => DpmForcedLevel::Auto,
         "low" => DpmForcedLevel::Low,
@@ -249,7 +698,7 @@ fn perf_level_from_str(s: &str) -> Option<DpmForcedLevel> {
     Some(perf_level)
 }
 
-fn power_profile_from_str(s: &str) -> Option<PowerProfile> {
+pub fn power_profile_from_str(s: &str) -> Option<PowerProfile> {
     let pp = match s {
         "BOOTUP_DEFAULT" => PowerProfile::BOOTUP_DEFAULT,
         "3D_FULL_SCREEN" => PowerProfile::FULLSCREEN3D,
@@ -266,3 +715,7 @@ fn power_profile_from_str(s: &str) -> Option<PowerProfile> {
 
     Some(pp)
 }
+
+pub fn power_profile_to_str(profile: PowerProfile) -> Option<&'static str> {
+    PROFILE_LIST.iter().copied().find(|s| power_profile_from_str(s) == Some(profile))
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::amdgpu_device::HwmonPwm;
+use crate::config::SoftwareFanCurve;
+
+// https://docs.kernel.org/hwmon/sysfs-interface.html
+const PWM_ENABLE_MANUAL: &str = "1";
+const PWM_ENABLE_AUTO: &str = "2";
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Drives hwmon `pwm1` in manual mode from `SoftwareFanCurve`.
+/// `pwm1_enable` is returned to automatic when the curve is removed, on failure and on drop.
+#[derive(Debug)]
+pub struct SoftwareFanControl {
+    hwmon_pwm: HwmonPwm,
+    curve: Option<SoftwareFanCurve>,
+    is_manual: bool,
+    failed: bool,
+    fan_speed: Option<u8>, // [%]
+    ref_temp: u32, // temperature when the fan speed was first read or last raised
+    updated_at: Option<Instant>,
+}
+
+impl SoftwareFanControl {
+    pub fn new(hwmon_pwm: HwmonPwm) -> Self {
+        Self {
+            hwmon_pwm,
+            curve: None,
+            is_manual: false,
+            failed: false,
+            fan_speed: None,
+            ref_temp: 0,
+            updated_at: None,
+        }
+    }
+
+    pub fn set_curve(&mut self, curve: Option<&SoftwareFanCurve>) {
+        if self.curve.as_ref() == curve {
+            return;
+        }
+
+        self.curve = curve.cloned();
+        self.failed = false;
+        self.updated_at = None;
+
+        if self.curve.is_none() {
+            self.release();
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if self.failed {
+            return;
+        }
+
+        let Some(ref curve) = self.curve else { return };
+
+        if self.updated_at.is_some_and(|t| t.elapsed() < INTERVAL) {
+            return;
+        }
+
+        self.updated_at = Some(Instant::now());
+
+        let Some(temp) = self.hwmon_pwm.read_temp(curve.sensor()) else {
+            debug!("Failed to read the {} temperature, return the fan control to automatic", curve.sensor());
+            self.fail();
+            return;
+        };
+        let fan_speed = self.next_fan_speed(temp, curve.fan_speed(temp));
+
+        if let Err(e) = self.write_fan_speed(fan_speed) {
+            debug!("Failed to set the fan speed ({e:?}), return the fan control to automatic");
+            self.fail();
+        }
+    }
+
+    fn next_fan_speed(&mut self, temp: u32, target: u8) -> u8 {
+        let Some(ref curve) = self.curve else { return target };
+        let hysteresis = curve.hysteresis.unwrap_or(SoftwareFanCurve::DEFAULT_HYSTERESIS) as u32;
+        let max_step = curve.max_step.unwrap_or(SoftwareFanCurve::DEFAULT_MAX_STEP);
+        let current = match self.fan_speed {
+            Some(fan_speed) => fan_speed,
+            None => {
+                // The first sample is the reference to slow down the fan from the initial speed.
+                self.ref_temp = temp;
+                *self.fan_speed.insert(
+                    self.hwmon_pwm.read_pwm().map(|pwm| (pwm * 100 / 255) as u8).unwrap_or(100)
+                )
+            },
+        };
+
+        if target > current {
+            self.ref_temp = temp;
+            current.saturating_add(max_step).min(target)
+        } else if target < current && temp + hysteresis <= self.ref_temp {
+            current.saturating_sub(max_step).max(target)
+        } else {
+            current
+        }
+    }
+
+    fn write_fan_speed(&mut self, fan_speed: u8) -> io::Result<()> {
+        if !self.is_manual {
+            debug!("Set pwm1_enable to manual");
+            fs::write(&self.hwmon_pwm.pwm_enable_path, PWM_ENABLE_MANUAL)?;
+            self.is_manual = true;
+        }
+
+        let pwm = (fan_speed as u32 * 255 + 50) / 100;
+
+        if self.fan_speed != Some(fan_speed) || self.hwmon_pwm.read_pwm() != Some(pwm) {
+            debug!("    Set fan speed ({fan_speed}%, pwm: {pwm})");
+            fs::write(&self.hwmon_pwm.pwm_path, pwm.to_string())?;
+        }
+
+        self.fan_speed = Some(fan_speed);
+
+        Ok(())
+    }
+
+    fn fail(&mut self) {
+        self.failed = true;
+        self.release();
+    }
+
+    /// Returns `pwm1_enable` to automatic.
+    pub fn release(&mut self) {
+        self.fan_speed = None;
+        self.ref_temp = 0; // taken again from the first sample after `fan_speed` is reset
+
+        if !self.is_manual {
+            return;
+        }
+
+        debug!("Set pwm1_enable to automatic");
+
+        if let Err(e) = fs::write(&self.hwmon_pwm.pwm_enable_path, PWM_ENABLE_AUTO) {
+            debug!("Failed to set pwm1_enable to automatic ({e:?})");
+        }
+
+        self.is_manual = false;
+    }
+}
+
+impl Drop for SoftwareFanControl {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use libdrm_amdgpu_sys::PCI;
+use ron::{de, ser};
+use serde::{Deserialize, Serialize};
+
+use crate::app::EffectiveConfig;
+use crate::backend::Backend;
+
+const JOURNAL_DIR: &str = "/run/amdgpu-profile-switcher";
+
+/// State of a device persisted in `/run`, so the next instance can recover after a crash.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Journal {
+    pub pid: u32,
+    pub snapshot: EffectiveConfig, // hardware state before any change
+    pub applied_entries: Vec<String>, // names of the applied (merged) entries
+}
+
+impl Journal {
+    fn path(pci_bus: &PCI::BUS_INFO) -> PathBuf {
+        Backend::get().path(JOURNAL_DIR).join(format!("{pci_bus}.ron"))
+    }
+
+    pub fn load(pci_bus: &PCI::BUS_INFO) -> Option<Self> {
+        let s = fs::read_to_string(Self::path(pci_bus)).ok()?;
+
+        de::from_str(&s).ok()
+    }
+
+    /// Writes to a temporary file and renames it, so the journal is never left half-written.
+    pub fn save(&self, pci_bus: &PCI::BUS_INFO) -> io::Result<()> {
+        let path = Self::path(pci_bus);
+        let tmp_path = path.with_extension("ron.tmp");
+        let s = ser::to_string_pretty(self, Default::default()).map_err(io::Error::other)?;
+
+        fs::create_dir_all(Backend::get().path(JOURNAL_DIR))?;
+        fs::write(&tmp_path, s)?;
+        fs::rename(&tmp_path, &path)
+    }
+
+    pub fn remove(pci_bus: &PCI::BUS_INFO) {
+        let _ = fs::remove_file(Self::path(pci_bus));
+    }
+}
+
+/// `DpmForcedLevel` as the string in `power_dpm_force_performance_level`
+pub mod serde_perf_level {
+    use libdrm_amdgpu_sys::AMDGPU::DpmForcedLevel;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    use crate::config::perf_level_from_str;
+
+    pub fn serialize<S: Serializer>(perf_level: &DpmForcedLevel, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(perf_level.to_arg())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<DpmForcedLevel, D::Error> {
+        let s = String::deserialize(d)?;
+
+        perf_level_from_str(&s).ok_or_else(|| D::Error::custom(format!("invalid perf_level: {s:?}")))
+    }
+}
+
+/// `PowerProfile` as the name in the config file
+pub mod serde_power_profile {
+    use libdrm_amdgpu_sys::AMDGPU::PowerProfile;
+    use serde::{Deserialize, Deserializer, Serializer, ser, de::Error};
+
+    use crate::config::{power_profile_from_str, power_profile_to_str};
+
+    pub fn serialize<S: Serializer>(profile: &PowerProfile, s: S) -> Result<S::Ok, S::Error> {
+        let name = power_profile_to_str(*profile)
+            .ok_or_else(|| <S::Error as ser::Error>::custom(format!("unknown profile: {profile:?}")))?;
+
+        s.serialize_str(name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<PowerProfile, D::Error> {
+        let s = String::deserialize(d)?;
+
+        power_profile_from_str(&s).ok_or_else(|| D::Error::custom(format!("invalid profile: {s:?}")))
+    }
+}
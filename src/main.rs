@@ -1,8 +1,6 @@
 use std::sync::atomic::Ordering;
 use std::cell::OnceCell;
 
-use libdrm_amdgpu_sys::AMDGPU;
-
 use proc_prog_name::ProcProgEntry;
 use log::debug;
 
@@ -17,8 +15,23 @@ use args::{AppMode, MainOpt, SubCommand};
 
 mod utils;
 
+mod matcher;
+
+mod process;
+use process::ProcTree;
+
+mod proc_event;
+use proc_event::ProcEventSource;
+
+mod backend;
+use backend::Backend;
+
+mod fan_control;
+mod journal;
+mod signal;
+
 mod app;
-use app::AppDevice;
+use app::{AppDevice, Switch};
 
 macro_rules! pci_list {
     ($pci_devs:expr, $config_device_pci:expr) => {
@@ -58,9 +71,9 @@ fn main() {
                     .iter_mut()
                     .find(|device| pci == device.pci.parse().unwrap())
                 {
-                    config_device.entries.insert(0, entry);
+                    config_device.entries.insert(0, *entry);
                 } else {
-                    let pci_devs = AMDGPU::get_all_amdgpu_pci_bus();
+                    let pci_devs = Backend::get().get_all_amdgpu_pci_bus();
 
                     if !pci_devs.iter().any(|pci_dev| pci_dev == &pci) {
                         pci_list!(pci_devs, pci);
@@ -73,6 +86,7 @@ fn main() {
                         _power_cap_watt_range: None,
                         default_perf_level: None,
                         default_profile: None,
+                        custom_profile: None,
                         default_fan_target_temperature: None,
                         _fan_target_temperature_range: None,
                         default_fan_minimum_pwm: None,
@@ -81,13 +95,33 @@ fn main() {
                         _sclk_offset_range: None,
                         vddgfx_offset: None,
                         _vddgfx_offset_range: None,
+                        min_sclk: None,
+                        max_sclk: None,
+                        _sclk_range: None,
+                        min_mclk: None,
+                        max_mclk: None,
+                        _mclk_range: None,
+                        vddc_curve: None,
+                        _vddc_curve_range: None,
+                        sclk_states: None,
+                        _sclk_states_range: None,
+                        mclk_states: None,
+                        _mclk_states_range: None,
+                        _vddc_range: None,
+                        sclk_od: None,
+                        mclk_od: None,
+                        dpm_levels: None,
+                        _dpm_levels: None,
                         fan_zero_rpm: None,
                         acoustic_target_rpm_threshold: None,
                         _acoustic_target_rpm_threshold_range: None,
                         fan_curve_points: None,
                         _fan_curve_temp_range: None,
                         _fan_curve_fan_speed_range: None,
-                        entries: vec![entry],
+                        software_fan_curve: None,
+                        min_dwell_secs: None,
+                        restore_on_exit: None,
+                        entries: vec![*entry],
                     };
 
                     config.config_devices.push(add_config_device);
@@ -102,7 +136,7 @@ fn main() {
 
         match main_opt.app_mode {
             AppMode::DumpProcs => {
-                let procs = ProcProgEntry::get_all_proc_prog_entries();
+                let procs = Backend::get().get_all_proc_prog_entries();
                 let procs: Vec<_> = procs.iter().map(|p| p.name.clone()).collect();
                 println!("{procs:#?}");
                 return;
@@ -112,6 +146,30 @@ fn main() {
                 let config = utils::load_config(&config_path).unwrap();
                 println!("config_path: {config_path:?}");
                 println!("{config:#?}");
+
+                for config_device in &config.config_devices {
+                    println!("{}: entries in resolved order", config_device.pci);
+
+                    for (i, e) in config_device.entries.iter().enumerate() {
+                        println!("    {i}: {:?} (priority: {}, merge: {})", e.name, e.priority, e.merge);
+                    }
+
+                    let Some(fan_curve) = AmdgpuDevice::get_from_pci_bus(config_device.pci)
+                        .and_then(|amdgpu_device| amdgpu_device.fan_curve)
+                        else { continue };
+
+                    println!("{}: fan_curve points to be written", config_device.pci);
+
+                    let curves = std::iter::once(("(default)", &config_device.fan_curve_points))
+                        .chain(config_device.entries.iter().map(|e| (e.name.as_str(), &e.fan_curve_points)));
+
+                    for (name, points) in curves {
+                        let Some(points) = points.as_deref().filter(|p| AppDevice::edited_fan_curve(p))
+                            else { continue };
+
+                        println!("    {name:?}: {:?}", fan_curve.resample(points));
+                    }
+                }
                 return;
             },
             AppMode::GenerateConfig => {
@@ -120,7 +178,7 @@ fn main() {
                 return;
             },
             AppMode::DumpSupportedPowerProfile => {
-                let pci_devs = AMDGPU::get_all_amdgpu_pci_bus();
+                let pci_devs = Backend::get().get_all_amdgpu_pci_bus();
 
                 for pci in pci_devs {
                     let Some(amdgpu_device) = AmdgpuDevice::get_from_pci_bus(pci) else {
@@ -136,12 +194,26 @@ fn main() {
                         amdgpu_device.pci_bus,
                         profiles,
                     );
+
+                    if let Some(table) = amdgpu_device.get_power_profile_table() {
+                        println!("heuristics ({:?}): {:?}", table.layout, table.columns);
+
+                        for p in &table.profiles {
+                            let active = if p.active { "*" } else { "" };
+                            println!("    {} {}{active}:", p.index, p.name);
+
+                            for (i, row) in p.rows.iter().enumerate() {
+                                let row_name = table.row_names.get(i).map(|s| s.as_str()).unwrap_or_default();
+                                println!("        {row_name:>8} {row:?}");
+                            }
+                        }
+                    }
                 }
 
                 return;
             },
             AppMode::DeviceList => {
-                let pci_devs = AMDGPU::get_all_amdgpu_pci_bus();
+                let pci_devs = Backend::get().get_all_amdgpu_pci_bus();
 
                 for pci in pci_devs {
                     let Some(amdgpu_device) = AmdgpuDevice::get_from_pci_bus(pci) else {
@@ -163,7 +235,7 @@ fn main() {
         }
     }
 
-    let pci_devs = AMDGPU::get_all_amdgpu_pci_bus();
+    let pci_devs = Backend::get().get_all_amdgpu_pci_bus();
     let config_path = config_path.unwrap();
 
     if pci_devs.is_empty() {
@@ -179,12 +251,7 @@ fn main() {
             let amdgpu_device = AmdgpuDevice::get_from_pci_bus(*pci)?;
             let config_device = config_device.clone();
 
-            Some(AppDevice {
-                amdgpu_device,
-                config_device,
-                cache_pid: None,
-                changed_default_config: false,
-            })
+            Some(AppDevice::new(amdgpu_device, config_device))
         }).collect()
     };
 
@@ -195,7 +262,9 @@ fn main() {
     env_logger::init();
     debug!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
-    for app in &app_devices {
+    signal::install_handlers();
+
+    for app in app_devices.iter_mut() {
         let once_log = OnceCell::new();
         'wait: loop {
             if !app.amdgpu_device.check_if_device_is_active() {
@@ -216,7 +285,11 @@ fn main() {
             panic!("Error: PermissionDenied for sysfs");
         }
 
-        app.set_default_od_config().unwrap();
+        if !app.resume_journal_entries() {
+            let _ = app.set_default_od_config();
+        }
+
+        app.save_journal();
     }
 
     let modified = utils::watch_config_file(&config_path);
@@ -224,9 +297,21 @@ fn main() {
     debug!("run loop");
 
     let mut name_list: Vec<String> = app_devices.iter().flat_map(|app| app.name_list()).collect();
+    let mut has_name_pattern = app_devices.iter().any(|app| app.config_device.has_name_pattern());
+    let mut has_descendants_entry = app_devices.iter().any(|app| app.config_device.has_descendants_entry());
     let mut procs: Vec<ProcProgEntry> = Vec::with_capacity(name_list.len());
+    let mut proc_tree = ProcTree::default();
+    let mut proc_events = ProcEventSource::new();
+    let mut rescan_procs = true;
+
+    proc_events.set_watch_fork(has_descendants_entry);
+
+    'run: loop {
+        if signal::is_terminated() {
+            debug!("Terminated");
+            break 'run;
+        }
 
-    loop {
         if modified.load(Ordering::Acquire) {
             debug!("Reload config file");
             let config = loop {
@@ -235,8 +320,18 @@ fn main() {
                     Err(_) => {
                         modified.store(false, Ordering::Release);
 
+                        // The fan is not controlled until the config file is fixed.
+                        for app in app_devices.iter_mut() {
+                            if let Some(ref mut fan_control) = app.fan_control {
+                                fan_control.release();
+                            }
+                        }
+
                         'wait: loop {
                             std::thread::sleep(std::time::Duration::from_secs(1));
+                            if signal::is_terminated() {
+                                break 'run;
+                            }
                             if modified.load(Ordering::Acquire) {
                                 break 'wait;
                             }
@@ -246,6 +341,8 @@ fn main() {
             };
 
             name_list.clear();
+            has_name_pattern = false;
+            has_descendants_entry = false;
 
             for config_device in &config.config_devices {
                 if let Some(ref mut app) = app_devices
@@ -255,12 +352,10 @@ fn main() {
                     app.check_changed_default_config(config_device);
                     app.config_device.clone_from(config_device);
                 } else if let Some(pci) = pci_devs.iter().find(|&pci_dev| pci_dev == &config_device.pci) {
-                    let new_app = AppDevice {
-                        amdgpu_device: AmdgpuDevice::get_from_pci_bus(*pci).unwrap(),
-                        config_device: config_device.clone(),
-                        cache_pid: None,
-                        changed_default_config: false,
-                    };
+                    let new_app = AppDevice::new(
+                        AmdgpuDevice::get_from_pci_bus(*pci).unwrap(),
+                        config_device.clone(),
+                    );
 
                     app_devices.push(new_app);
                 } else {
@@ -268,13 +363,35 @@ fn main() {
                 }
 
                 name_list.extend(config_device.names());
+                has_name_pattern |= config_device.has_name_pattern();
+                has_descendants_entry |= config_device.has_descendants_entry();
             }
 
+            proc_events.set_watch_fork(has_descendants_entry);
             modified.store(false, Ordering::Release);
+            rescan_procs = true;
         }
 
-        if !name_list.is_empty() {
-            ProcProgEntry::update_entries_with_name_filter(&mut procs, &name_list);
+        if rescan_procs {
+            procs.clear();
+
+            if has_name_pattern || has_descendants_entry {
+                // glob/regex patterns can't be passed to the name filter,
+                // and descendants of the target process may have any name.
+                procs = Backend::get().get_all_proc_prog_entries();
+            } else if !name_list.is_empty() {
+                Backend::get().update_entries_with_name_filter(&mut procs, &name_list);
+            }
+
+            proc_tree = if has_descendants_entry {
+                ProcTree::from_procs(&procs)
+            } else {
+                ProcTree::default()
+            };
+
+            for app in app_devices.iter_mut() {
+                app.clear_entry_pids();
+            }
         }
 
         'device: for app in app_devices.iter_mut() {
@@ -289,57 +406,84 @@ fn main() {
                     app.amdgpu_device.device_name,
                 );
 
-                let _ = app.set_default_od_config();
-                app.cache_pid = None;
-                app.changed_default_config = false;
+                // Rolled back: retry at the next tick.
+                if app.set_default_od_config().is_ok() {
+                    app.clear_cache();
+                    app.changed_default_config = false;
+                    app.save_journal();
+                }
             }
 
+            app.update_fan_control();
+
             if app.config_device.entries.is_empty() {
                 continue 'device;
             }
 
-            let mut apply_config_entry: Option<ParsedConfigEntry> = None;
-            let mut pid: Option<i32> = None;
+            let matched_entries = app.detect_entries(&procs, &proc_tree);
+            let apply_config_entry = ParsedConfigEntry::merge_entries(matched_entries.iter().map(|(e, _pids)| e));
+            let pids = matched_entries.first().map(|(_e, pids)| pids.clone()).unwrap_or_default();
 
-            'detect: for e in &app.config_device.entries {
-                if let Some(proc) = procs.iter().find(|p| e.name == p.name) {
-                    apply_config_entry = Some(e.clone());
-                    pid = Some(proc.pid);
-                    break 'detect;
-                }
-            }
+            app.matched_entries = matched_entries;
 
-            if app.cache_pid.is_some() && pid == app.cache_pid {
-                continue 'device;
-            }
+            let is_applied = apply_config_entry.is_some() && apply_config_entry == app.cache_entry;
+            // restored if the switch fails, so the next tick retries without waiting again
+            let switch_state = app.switch_state.clone();
 
-            if let Some(apply_config) = &apply_config_entry {
-                debug!(
-                    "{} ({}): Detected target process: {:?} (pid: {pid:?})",
-                    app.amdgpu_device.pci_bus,
-                    app.amdgpu_device.device_name,
-                    apply_config.name,
-                );
-                if let Err((e, s)) = app.apply_config(apply_config) {
+            match app.next_switch(apply_config_entry) {
+                Switch::Keep => {
+                    if is_applied && !app.cache_pid.is_some_and(|cache_pid| pids.contains(&cache_pid)) {
+                        app.cache_pid = pids.first().copied();
+                    }
+                },
+                Switch::Apply(apply_config) => {
                     debug!(
-                        "{} ({}): Failed to set {s} for {:?} ({e:?})",
+                        "{} ({}): Detected target process: {:?} (pid: {:?})",
                         app.amdgpu_device.pci_bus,
                         app.amdgpu_device.device_name,
                         apply_config.name,
+                        pids.first(),
                     );
-                }
-                app.cache_pid = pid;
-            } else if app.cache_pid.is_some() {
-                debug!(
-                    "Target process (pid: {:?}) exited. Default settings restoration started.",
-                    app.cache_pid,
-                );
-                let _ = app.set_default_od_config();
-                app.cache_pid = None;
+                    if let Err(e) = app.apply_config(&apply_config) {
+                        // Rolled back: keep the previous entry, so the next tick retries.
+                        debug!(
+                            "{} ({}): Failed to apply {:?} ({e})",
+                            app.amdgpu_device.pci_bus,
+                            app.amdgpu_device.device_name,
+                            apply_config.name,
+                        );
+                        app.switch_state = switch_state;
+                        continue 'device;
+                    }
+                    app.cache_pid = pids.first().copied();
+                    app.cache_entry = Some(*apply_config);
+                    app.applied_entries = app.matched_entries.iter().map(|(e, _pids)| e.name.clone()).collect();
+                    app.save_journal();
+                },
+                Switch::Restore => {
+                    debug!(
+                        "Target process (pid: {:?}) exited. Default settings restoration started.",
+                        app.cache_pid,
+                    );
+                    if app.set_default_od_config().is_err() {
+                        // Rolled back: the entry is still applied, so the next tick retries.
+                        app.switch_state = switch_state;
+                        continue 'device;
+                    }
+                    app.clear_cache();
+                    app.save_journal();
+                },
             }
         }
 
-        procs.clear();
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        rescan_procs = proc_events.wait(std::time::Duration::from_secs(1));
+    }
+
+    for app in app_devices.iter_mut() {
+        if app.config_device.restore_on_exit {
+            app.restore_snapshot();
+            // The device is back in the original state, so there is nothing to recover.
+            app.remove_journal();
+        }
     }
 }
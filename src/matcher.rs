@@ -0,0 +1,99 @@
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+pub enum NameMatcher {
+    Exact(String),
+    Glob(Regex),
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    pub fn exact(name: &str) -> Self {
+        Self::Exact(name.to_string())
+    }
+
+    pub fn glob(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(&glob_to_regex(pattern)).map(Self::Glob)
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self::Regex)
+    }
+
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Self::Exact(_))
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            Self::Exact(s) => s == name,
+            Self::Glob(re) | Self::Regex(re) => re.is_match(name),
+        }
+    }
+}
+
+impl PartialEq for NameMatcher {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Exact(a), Self::Exact(b)) => a == b,
+            (Self::Glob(a), Self::Glob(b)) |
+            (Self::Regex(a), Self::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for NameMatcher {}
+
+/// `*` matches any sequence, `?` matches any single character,
+/// and `[...]`/`[!...]` match a character class.
+fn glob_to_regex(pattern: &str) -> String {
+    let mut re = String::with_capacity(pattern.len() * 2 + 2);
+    let mut chars = pattern.chars().peekable();
+    let mut in_class = false;
+
+    re.push('^');
+
+    while let Some(c) = chars.next() {
+        if in_class {
+            match c {
+                ']' => {
+                    in_class = false;
+                    re.push(']');
+                },
+                '\\' | '[' | '&' | '~' => {
+                    re.push('\\');
+                    re.push(c);
+                },
+                _ => re.push(c),
+            }
+
+            continue;
+        }
+
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '[' if chars.clone().any(|c| c == ']') => {
+                in_class = true;
+                re.push('[');
+
+                if chars.next_if_eq(&'!').is_some() {
+                    re.push('^');
+                }
+                if chars.next_if_eq(&']').is_some() {
+                    re.push_str("\\]");
+                }
+            },
+            _ => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    if in_class {
+        re.push(']');
+    }
+
+    re.push('$');
+
+    re
+}
@@ -0,0 +1,263 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::backend::Backend;
+
+// linux/connector.h
+const CN_IDX_PROC: u32 = 0x1;
+const CN_VAL_PROC: u32 = 0x1;
+// linux/cn_proc.h
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_FORK: u32 = 0x00000001;
+const PROC_EVENT_EXEC: u32 = 0x00000002;
+const PROC_EVENT_COMM: u32 = 0x00000200;
+const PROC_EVENT_EXIT: u32 = 0x80000000;
+
+const NLMSG_HDR_SIZE: usize = 16;
+const CN_MSG_SIZE: usize = 20;
+// proc_event.what, cpu, timestamp_ns
+const PROC_EVENT_HDR_SIZE: usize = 16;
+
+/// A process often execs/exits together with others (e.g. launcher scripts),
+/// so the process list is rescanned at most once per this interval.
+const MIN_RESCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Notifies when the process list may have been changed.
+pub enum ProcEventSource {
+    /// netlink proc connector (requires CAP_NET_ADMIN)
+    Netlink {
+        rx: mpsc::Receiver<()>,
+        last_rescan: Instant,
+        watch_fork: Arc<AtomicBool>,
+    },
+    /// rescan `/proc` every time
+    Polling,
+}
+
+impl ProcEventSource {
+    /// Use the netlink proc connector if available, otherwise fall back to polling.
+    /// A captured tree (`APS_SYSFS_ROOT`) is always polled, the events are of the host.
+    pub fn new() -> Self {
+        if let Backend::Root(_) = Backend::get() {
+            debug!("Poll the process list under {}", Backend::ENV_ROOT);
+            return Self::Polling;
+        }
+
+        match ProcConnector::connect() {
+            Ok(connector) => {
+                debug!("Use the netlink proc connector for process detection");
+                let (tx, rx) = mpsc::channel();
+                let watch_fork = Arc::new(AtomicBool::new(false));
+                let watch_fork_ = watch_fork.clone();
+
+                std::thread::spawn(move || connector.run(tx, watch_fork_));
+
+                Self::Netlink { rx, last_rescan: Instant::now(), watch_fork }
+            },
+            Err(e) => {
+                debug!("netlink proc connector is unavailable ({e:?}), fall back to polling");
+                Self::Polling
+            },
+        }
+    }
+
+    /// Also notify new processes (fork without exec),
+    /// so descendants of the target processes are tracked as with polling.
+    pub fn set_watch_fork(&self, watch_fork: bool) {
+        if let Self::Netlink { watch_fork: flag, .. } = self {
+            flag.store(watch_fork, Ordering::Release);
+        }
+    }
+
+    /// Waits for process events up to `timeout`.
+    /// Returns `true` if the process list needs to be rescanned.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        let Self::Netlink { rx, last_rescan, .. } = self else {
+            std::thread::sleep(timeout);
+            return true;
+        };
+
+        match rx.recv_timeout(timeout) {
+            Ok(()) => {
+                if let Some(remaining) = MIN_RESCAN_INTERVAL.checked_sub(last_rescan.elapsed()) {
+                    std::thread::sleep(remaining);
+                }
+
+                while rx.try_recv().is_ok() {}
+                *last_rescan = Instant::now();
+
+                true
+            },
+            Err(mpsc::RecvTimeoutError::Timeout) => false,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                debug!("netlink proc connector is disconnected, fall back to polling");
+                *self = Self::Polling;
+
+                true
+            },
+        }
+    }
+}
+
+struct ProcConnector {
+    fd: OwnedFd,
+}
+
+impl ProcConnector {
+    fn connect() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let pid = std::process::id();
+
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_pid = pid;
+        addr.nl_groups = CN_IDX_PROC;
+
+        let r = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+
+        if r < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // nlmsghdr + cn_msg + proc_cn_mcast_op
+        let mut msg: Vec<u8> = Vec::with_capacity(NLMSG_HDR_SIZE + CN_MSG_SIZE + 4);
+        let len = (NLMSG_HDR_SIZE + CN_MSG_SIZE + 4) as u32;
+
+        msg.extend(len.to_ne_bytes()); // nlmsg_len
+        msg.extend((libc::NLMSG_DONE as u16).to_ne_bytes()); // nlmsg_type
+        msg.extend(0u16.to_ne_bytes()); // nlmsg_flags
+        msg.extend(0u32.to_ne_bytes()); // nlmsg_seq
+        msg.extend(pid.to_ne_bytes()); // nlmsg_pid
+        msg.extend(CN_IDX_PROC.to_ne_bytes()); // id.idx
+        msg.extend(CN_VAL_PROC.to_ne_bytes()); // id.val
+        msg.extend(0u32.to_ne_bytes()); // seq
+        msg.extend(0u32.to_ne_bytes()); // ack
+        msg.extend(4u16.to_ne_bytes()); // len
+        msg.extend(0u16.to_ne_bytes()); // flags
+        msg.extend(PROC_CN_MCAST_LISTEN.to_ne_bytes());
+
+        let r = unsafe {
+            libc::send(fd.as_raw_fd(), msg.as_ptr() as *const libc::c_void, msg.len(), 0)
+        };
+
+        if r < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { fd })
+    }
+
+    fn run(self, tx: mpsc::Sender<()>, watch_fork: Arc<AtomicBool>) {
+        let mut buf = [0u8; 4096];
+
+        loop {
+            let len = unsafe {
+                libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+            };
+
+            if len < 0 {
+                let e = io::Error::last_os_error();
+
+                match e.raw_os_error() {
+                    Some(libc::EINTR) => continue,
+                    // Some events were dropped, so notify to rescan.
+                    Some(libc::ENOBUFS) => {
+                        if tx.send(()).is_err() { return }
+                        continue;
+                    },
+                    _ => {
+                        debug!("Failed to receive from the netlink proc connector ({e:?})");
+                        return;
+                    },
+                }
+            }
+
+            if len == 0 {
+                return;
+            }
+
+            let watch_fork = watch_fork.load(Ordering::Acquire);
+
+            if Self::has_target_event(&buf[..len as usize], watch_fork) && tx.send(()).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn has_target_event(buf: &[u8], watch_fork: bool) -> bool {
+        let mut msgs = buf;
+
+        while msgs.len() >= NLMSG_HDR_SIZE {
+            let Some(nlmsg_len) = read_u32(msgs, 0) else { break };
+            let nlmsg_len = nlmsg_len as usize;
+
+            if nlmsg_len < NLMSG_HDR_SIZE || nlmsg_len > msgs.len() {
+                break;
+            }
+
+            let msg = &msgs[..nlmsg_len];
+            let event = NLMSG_HDR_SIZE + CN_MSG_SIZE;
+
+            // proc_event.what
+            if let Some(what) = read_u32(msg, event)
+                && matches!(what, PROC_EVENT_EXEC | PROC_EVENT_COMM | PROC_EVENT_EXIT)
+            {
+                // exec/comm/exit events start with process_pid and process_tgid.
+                // Threads are not listed in `/proc`, so ignore their comm/exit events.
+                let pid = read_u32(msg, event + PROC_EVENT_HDR_SIZE);
+                let tgid = read_u32(msg, event + PROC_EVENT_HDR_SIZE + 4);
+
+                if what == PROC_EVENT_EXEC || pid == tgid {
+                    return true;
+                }
+            }
+
+            // fork events: parent_pid, parent_tgid, child_pid, child_tgid
+            // A new process (not a thread) is the thread-group leader.
+            if watch_fork && read_u32(msg, event) == Some(PROC_EVENT_FORK) {
+                let child_pid = read_u32(msg, event + PROC_EVENT_HDR_SIZE + 8);
+                let child_tgid = read_u32(msg, event + PROC_EVENT_HDR_SIZE + 12);
+
+                if child_pid == child_tgid {
+                    return true;
+                }
+            }
+
+            // NLMSG_ALIGN
+            let aligned_len = (nlmsg_len + 3) & !3;
+            msgs = msgs.get(aligned_len..).unwrap_or_default();
+        }
+
+        false
+    }
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset+4)?;
+
+    Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+}
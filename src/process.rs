@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use libdrm_amdgpu_sys::PCI;
+use proc_prog_name::ProcProgEntry;
+
+use crate::backend::Backend;
+
+fn proc_pid_path(pid: i32) -> PathBuf {
+    Backend::get().path(format!("/proc/{pid}"))
+}
+
+/// Arguments in `/proc/<pid>/cmdline`, joined with spaces.
+pub fn get_cmdline(pid: i32) -> Option<String> {
+    let buf = fs::read(proc_pid_path(pid).join("cmdline")).ok()?;
+    let args: Vec<_> = buf
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect();
+
+    Some(args.join(" "))
+}
+
+pub fn get_exe_path(pid: i32) -> Option<PathBuf> {
+    let path = fs::read_link(proc_pid_path(pid).join("exe")).ok()?;
+
+    // " (deleted)" is appended if the executable was replaced (e.g. updated) after exec.
+    match path.to_str().and_then(|s| s.strip_suffix(" (deleted)")) {
+        Some(s) => Some(PathBuf::from(s)),
+        None => Some(path),
+    }
+}
+
+/// Paths in `/proc/<pid>/cgroup` (e.g. `/user.slice/user-1000.slice/user@1000.service/app.slice/...`)
+pub fn get_cgroup_paths(pid: i32) -> Vec<String> {
+    let Ok(s) = fs::read_to_string(proc_pid_path(pid).join("cgroup")) else { return Vec::new() };
+
+    // "hierarchy-ID:controller-list:cgroup-path"
+    s.lines()
+        .filter_map(|l| l.splitn(3, ':').nth(2))
+        .map(|path| path.to_string())
+        .collect()
+}
+
+/// systemd units (`*.service`, `*.scope`) in the cgroup paths
+pub fn units_from_cgroup_paths(paths: &[String]) -> impl Iterator<Item = &str> {
+    paths
+        .iter()
+        .flat_map(|path| path.split('/'))
+        .filter(|c| c.ends_with(".service") || c.ends_with(".scope"))
+}
+
+/// `app-flatpak-<app id>-<instance>.scope`
+pub fn flatpak_id_from_cgroup_paths(paths: &[String]) -> Option<String> {
+    units_from_cgroup_paths(paths).find_map(|unit| {
+        let s = unit.strip_prefix("app-flatpak-")?.strip_suffix(".scope")?;
+        let (id, _instance) = s.rsplit_once('-')?;
+
+        Some(id.replace("\\x2d", "-"))
+    })
+}
+
+/// Reads `/proc/<pid>/environ` and returns the values of `keys`.
+pub fn get_environ_values<'a>(pid: i32, keys: impl Iterator<Item = &'a String>) -> Vec<Option<String>> {
+    let buf = fs::read(proc_pid_path(pid).join("environ")).unwrap_or_default();
+    let vars: Vec<_> = buf
+        .split(|b| *b == 0)
+        .filter_map(|var| {
+            let pos = var.iter().position(|b| *b == b'=')?;
+
+            Some((&var[..pos], &var[pos+1..]))
+        })
+        .collect();
+
+    keys.map(|key| {
+        vars.iter()
+            .find(|(k, _v)| *k == key.as_bytes())
+            .map(|(_k, v)| String::from_utf8_lossy(v).into_owned())
+    })
+    .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrmFdinfo {
+    pub pdev: PCI::BUS_INFO,
+    pub client_id: Option<u64>,
+    pub engine_gfx_ns: u64,
+    pub engine_compute_ns: u64,
+}
+
+impl DrmFdinfo {
+    fn from_str(s: &str) -> Option<Self> {
+        let mut pdev: Option<PCI::BUS_INFO> = None;
+        let mut client_id: Option<u64> = None;
+        let mut engine_gfx_ns = 0;
+        let mut engine_compute_ns = 0;
+
+        fn parse_ns(s: &str) -> u64 {
+            s.trim_end_matches(" ns").parse().unwrap_or(0)
+        }
+
+        for l in s.lines() {
+            let Some((key, val)) = l.split_once(':') else { continue };
+            let val = val.trim();
+
+            match key {
+                "drm-pdev" => pdev = val.parse().ok(),
+                "drm-client-id" => client_id = val.parse().ok(),
+                "drm-engine-gfx" => engine_gfx_ns = parse_ns(val),
+                "drm-engine-compute" => engine_compute_ns = parse_ns(val),
+                _ => {},
+            }
+        }
+
+        Some(Self {
+            pdev: pdev?,
+            client_id,
+            engine_gfx_ns,
+            engine_compute_ns,
+        })
+    }
+}
+
+/// DRM clients opened by the process, from `/proc/<pid>/fdinfo/<fd>`.
+/// Only fds pointing to `/dev/dri/*` are read.
+pub fn get_drm_fdinfo(pid: i32) -> Vec<DrmFdinfo> {
+    let path = proc_pid_path(pid);
+    let Ok(fds) = fs::read_dir(path.join("fd")) else { return Vec::new() };
+    // links in a captured tree may point to `/dev/dri` under the root
+    let dri_path = Backend::get().path("/dev/dri");
+    let mut list: Vec<DrmFdinfo> = Vec::new();
+
+    for fd in fds.flatten() {
+        let is_dri = fs::read_link(fd.path())
+            .is_ok_and(|link| link.starts_with("/dev/dri/") || link.starts_with(&dri_path));
+
+        if !is_dri {
+            continue;
+        }
+
+        let Some(info) = fs::read_to_string(path.join("fdinfo").join(fd.file_name()))
+            .ok()
+            .and_then(|s| DrmFdinfo::from_str(&s))
+            else { continue };
+
+        // dup'ed fds share the same DRM client
+        if info.client_id.is_some() && list.iter().any(|i| i.client_id == info.client_id) {
+            continue;
+        }
+
+        list.push(info);
+    }
+
+    list
+}
+
+pub fn uses_drm_device(pid: i32, pci_bus: &PCI::BUS_INFO) -> bool {
+    get_drm_fdinfo(pid).iter().any(|info| &info.pdev == pci_bus)
+}
+
+/// Samples the GFX/compute engine time of processes once per tick,
+/// and calculates the busy percentage from the previous tick.
+#[derive(Debug, Clone, Default)]
+pub struct GpuBusySampler {
+    prev: HashMap<i32, (u64, Instant)>,
+    current: HashMap<i32, (u64, Instant)>,
+}
+
+impl GpuBusySampler {
+    pub fn next_tick(&mut self) {
+        self.prev = std::mem::take(&mut self.current);
+    }
+
+    pub fn busy_percent(&mut self, pid: i32, pci_bus: &PCI::BUS_INFO) -> Option<f64> {
+        let (busy_ns, time) = *self.current.entry(pid).or_insert_with(|| {
+            let busy_ns = get_drm_fdinfo(pid)
+                .iter()
+                .filter(|info| &info.pdev == pci_bus)
+                .map(|info| info.engine_gfx_ns + info.engine_compute_ns)
+                .sum();
+
+            (busy_ns, Instant::now())
+        });
+        let (pre_busy_ns, pre_time) = self.prev.get(&pid)?;
+        let elapsed_ns = time.duration_since(*pre_time).as_nanos() as f64;
+
+        if elapsed_ns == 0.0 {
+            return None;
+        }
+
+        Some(busy_ns.saturating_sub(*pre_busy_ns) as f64 / elapsed_ns * 100.0)
+    }
+}
+
+fn get_status_field(pid: i32, key: &str) -> Option<String> {
+    let s = fs::read_to_string(proc_pid_path(pid).join("status")).ok()?;
+
+    s.lines()
+        .find_map(|l| l.strip_prefix(key)?.strip_prefix(':'))
+        .map(|v| v.trim().to_string())
+}
+
+fn get_ppid(pid: i32) -> Option<i32> {
+    get_status_field(pid, "PPid")?.parse().ok()
+}
+
+/// Real UID of the process
+pub fn get_uid(pid: i32) -> Option<u32> {
+    // "Uid: <real> <effective> <saved set> <filesystem>"
+    get_status_field(pid, "Uid")?.split_whitespace().next()?.parse().ok()
+}
+
+pub fn find_uid_by_user_name(user_name: &str) -> Option<u32> {
+    let s = fs::read_to_string(Backend::get().path("/etc/passwd")).ok()?;
+
+    // "name:password:UID:GID:GECOS:directory:shell"
+    s.lines().find_map(|l| {
+        let mut split = l.split(':');
+
+        if split.next()? != user_name {
+            return None;
+        }
+
+        split.nth(1)?.parse().ok()
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProcTree {
+    ppid: HashMap<i32, i32>,
+}
+
+impl ProcTree {
+    const MAX_DEPTH: usize = 64;
+
+    pub fn from_procs(procs: &[ProcProgEntry]) -> Self {
+        let ppid = procs
+            .iter()
+            .filter_map(|p| Some((p.pid, get_ppid(p.pid)?)))
+            .collect();
+
+        Self { ppid }
+    }
+
+    pub fn is_alive(&self, pid: i32) -> bool {
+        self.ppid.contains_key(&pid)
+    }
+
+    fn has_ancestor_in(&self, pid: i32, ancestors: &[i32]) -> bool {
+        let mut pid = pid;
+
+        for _ in 0..Self::MAX_DEPTH {
+            let Some(&ppid) = self.ppid.get(&pid) else { return false };
+
+            if ancestors.contains(&ppid) {
+                return true;
+            }
+
+            if ppid <= 1 {
+                return false;
+            }
+
+            pid = ppid;
+        }
+
+        false
+    }
+
+    /// Returns `pids` followed by all of their descendants.
+    pub fn with_descendants(&self, pids: &[i32]) -> Vec<i32> {
+        let mut list = pids.to_vec();
+
+        list.extend(
+            self.ppid
+                .keys()
+                .filter(|pid| !pids.contains(pid) && self.has_ancestor_in(**pid, pids))
+        );
+
+        list
+    }
+}
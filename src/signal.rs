@@ -0,0 +1,23 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handler(_signum: libc::c_int) {
+    TERMINATED.store(true, Ordering::Release);
+}
+
+/// Catches SIGINT, SIGTERM and SIGHUP, so the run loop can exit and release the device.
+pub fn install_handlers() {
+    for signum in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler as *const () as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signum, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+pub fn is_terminated() -> bool {
+    TERMINATED.load(Ordering::Acquire)
+}
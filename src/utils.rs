@@ -7,7 +7,7 @@ use log::{debug, error};
 
 use ron::{de, ser};
 
-use crate::{AMDGPU, AmdgpuDevice};
+use crate::{AmdgpuDevice, Backend};
 use crate::config::{Config, ConfigPerDevice, ConfigEntry, ParsedConfig, ParseConfigError};
 
 const CONFIG_FILE_NAME: &str = "amdgpu-profile-switcher.ron";
@@ -53,7 +53,7 @@ const PERF_LEVEL_LIST: &[&str] = &[
     "perf_determinism",
 ];
 
-const PROFILE_LIST: &[&str] = &[
+pub const PROFILE_LIST: &[&str] = &[
     "BOOTUP_DEFAULT",
     "3D_FULL_SCREEN",
     "POWER_SAVING",
@@ -66,6 +66,12 @@ const PROFILE_LIST: &[&str] = &[
     "UNCAPPED",
 ];
 
+const MATCH_MODE_LIST: &[&str] = &[
+    "exact",
+    "glob",
+    "regex",
+];
+
 pub fn load_raw_config(config_path: &Path) -> Config {
     let s = fs::read_to_string(config_path).unwrap();
 
@@ -118,6 +124,36 @@ pub fn load_config(config_path: &Path) -> Result<ParsedConfig, ParseConfigError>
                         .find(|(_i, l)| l.contains(invalid_profile))
                         .map(|(i, _l)| i);
                 },
+                ParseConfigError::InvalidMatchMode(ref invalid_match_mode) => {
+                    eprintln!("`match_mode` must be one of the following: {MATCH_MODE_LIST:?}");
+                    line_number = lines
+                        .find(|(_i, l)| l.contains(invalid_match_mode))
+                        .map(|(i, _l)| i);
+                },
+                ParseConfigError::InvalidPattern(ref pattern, ref reason) => {
+                    eprintln!("Invalid pattern: {pattern:?}\n{reason}");
+                    line_number = lines
+                        .find(|(_i, l)| l.contains(pattern.as_str()))
+                        .map(|(i, _l)| i);
+                },
+                ParseConfigError::RelativeExePath(ref path) => {
+                    eprintln!("`exe_path` must be an absolute path.");
+                    line_number = lines
+                        .find(|(_i, l)| l.contains(path.as_str()))
+                        .map(|(i, _l)| i);
+                },
+                ParseConfigError::UnknownUser(ref user_name) => {
+                    eprintln!("User {user_name:?} is not found in /etc/passwd.");
+                    line_number = lines
+                        .find(|(_i, l)| l.contains(user_name.as_str()))
+                        .map(|(i, _l)| i);
+                },
+                ParseConfigError::InvalidSoftwareFanCurve(ref reason) => {
+                    eprintln!("Invalid `software_fan_curve`: {reason}");
+                    line_number = lines
+                        .find(|(_i, l)| l.contains("software_fan_curve"))
+                        .map(|(i, _l)| i);
+                },
                 ParseConfigError::InvalidPci(ref pci) => {
                     line_number = lines
                         .find(|(_i, l)| l.contains(pci))
@@ -167,7 +203,9 @@ pub fn watch_config_file(config_path: &Path) -> Arc<AtomicBool> {
     is_modified
 }
 
-pub const COMMENT: &str = r#"// Config entries that are earlier take priority.
+pub const COMMENT: &str = r#"// Config entries with higher `priority` (default: 0) take precedence,
+// and entries that are earlier take priority among the same `priority`.
+// Entries with `merge: Some(true)` are combined field by field when they match at the same time.
 /*
     perf_level: [
         "auto",
@@ -192,11 +230,16 @@ pub const COMMENT: &str = r#"// Config entries that are earlier take priority.
         "CAPPED",
         "UNCAPPED",
     ],
+    match_mode: [
+        "exact", // default
+        "glob",
+        "regex",
+    ],
 */
 "#;
 
 pub fn generate_config() -> ron::Result<String> {
-    let pci_devs = AMDGPU::get_all_amdgpu_pci_bus();
+    let pci_devs = Backend::get().get_all_amdgpu_pci_bus();
 
     if pci_devs.is_empty() {
         panic!("No AMDGPU devices.");
@@ -204,12 +247,44 @@ pub fn generate_config() -> ron::Result<String> {
 
     let entry_example = ConfigEntry {
         name: "glxgears".to_string(),
+        match_mode: None,
+        cmdline_contains: None,
+        exe_path: None,
+        include_descendants: None,
+        cgroup: None,
+        unit: None,
+        flatpak_id: None,
+        env: None,
+        uid: None,
+        user: None,
+        require_device_usage: None,
+        min_gfx_busy_percent: None,
+        priority: None,
+        merge: None,
+        apply_delay_secs: None,
+        restore_grace_secs: None,
         perf_level: None,
         profile: Some("BOOTUP_DEFAULT".to_string()),
+        custom_profile: None,
         power_cap_watt: None,
         fan_target_temperature: None,
         fan_minimum_pwm: None,
         acoustic_target_rpm_threshold: None,
+        sclk_offset: None,
+        vddgfx_offset: None,
+        min_sclk: None,
+        max_sclk: None,
+        min_mclk: None,
+        max_mclk: None,
+        vddc_curve: None,
+        sclk_states: None,
+        mclk_states: None,
+        sclk_od: None,
+        mclk_od: None,
+        dpm_levels: None,
+        fan_zero_rpm: None,
+        fan_curve_points: None,
+        software_fan_curve: None,
     };
     let config_devices: Vec<_> = pci_devs
         .iter()
@@ -255,6 +330,34 @@ pub fn generate_config() -> ron::Result<String> {
                 .vddgfx_offset
                 .as_ref()
                 .and_then(|vddgfx| vddgfx.range);
+            let min_sclk = dev.sclk_limits.as_ref().and_then(|sclk| sclk.min);
+            let max_sclk = dev.sclk_limits.as_ref().and_then(|sclk| sclk.max);
+            let _sclk_range = dev.sclk_limits.as_ref().and_then(|sclk| sclk.range);
+            let min_mclk = dev.mclk_limits.as_ref().and_then(|mclk| mclk.min);
+            let max_mclk = dev.mclk_limits.as_ref().and_then(|mclk| mclk.max);
+            let _mclk_range = dev.mclk_limits.as_ref().and_then(|mclk| mclk.range);
+            let vddc_curve = dev.vddc_curve.as_ref().map(|curve| curve.points.clone());
+            let _vddc_curve_range = dev.vddc_curve.as_ref().map(|curve| curve.ranges.clone());
+            let sclk_states = dev.sclk_states.as_ref().map(|sclk| sclk.states.clone());
+            let _sclk_states_range = dev.sclk_states.as_ref().and_then(|sclk| sclk.clk_range);
+            let mclk_states = dev.mclk_states.as_ref().map(|mclk| mclk.states.clone());
+            let _mclk_states_range = dev.mclk_states.as_ref().and_then(|mclk| mclk.clk_range);
+            let _vddc_range = dev.sclk_states
+                .as_ref()
+                .or(dev.mclk_states.as_ref())
+                .and_then(|states| states.volt_range);
+            let sclk_od = dev.sclk_od.as_ref().map(|od| od.current);
+            let mclk_od = dev.mclk_od.as_ref().map(|od| od.current);
+            let _dpm_levels = (!dev.dpm_levels.is_empty()).then(|| {
+                dev.dpm_levels
+                    .iter()
+                    .map(|dpm| {
+                        let levels = dpm.levels.iter().map(|(i, clk)| format!("{i}: {clk}")).collect();
+
+                        (dpm.clk_type.name().to_string(), levels)
+                    })
+                    .collect()
+            });
             let acoustic_target_rpm_threshold = dev
                 .acoustic_target_rpm_threshold
                 .as_ref()
@@ -283,6 +386,7 @@ pub fn generate_config() -> ron::Result<String> {
                 _power_cap_watt_range,
                 default_perf_level: None,
                 default_profile: None,
+                custom_profile: None,
                 default_fan_target_temperature,
                 _fan_target_temperature_range,
                 default_fan_minimum_pwm,
@@ -291,12 +395,32 @@ pub fn generate_config() -> ron::Result<String> {
                 _sclk_offset_range,
                 vddgfx_offset,
                 _vddgfx_offset_range,
+                min_sclk,
+                max_sclk,
+                _sclk_range,
+                min_mclk,
+                max_mclk,
+                _mclk_range,
+                vddc_curve,
+                _vddc_curve_range,
+                sclk_states,
+                _sclk_states_range,
+                mclk_states,
+                _mclk_states_range,
+                _vddc_range,
+                sclk_od,
+                mclk_od,
+                dpm_levels: None,
+                _dpm_levels,
                 fan_zero_rpm: dev.fan_zero_rpm.map(|f| f.flag),
                 acoustic_target_rpm_threshold,
                 _acoustic_target_rpm_threshold_range,
                 fan_curve_points,
                 _fan_curve_temp_range,
                 _fan_curve_fan_speed_range,
+                software_fan_curve: None,
+                min_dwell_secs: None,
+                restore_on_exit: None,
                 entries: vec![entry_example.clone()],
             })
         })